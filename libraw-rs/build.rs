@@ -6,6 +6,36 @@ fn main() -> anyhow::Result<()> {
     #[cfg(feature = "exif")]
     libread(out_dir)?;
 
+    shim(out_dir)?;
+
+    Ok(())
+}
+
+/// C++ helpers for the parts of libraw which are not exposed in the c api
+pub fn shim(out_dir: impl AsRef<Path>) -> anyhow::Result<()> {
+    println!("cargo:rerun-if-changed=shim");
+    let mut shim = cc::Build::new();
+
+    let includes = std::env::var("DEP_RAW_R_INCLUDE")?;
+    let includes = std::env::split_paths(&includes).collect::<Vec<_>>();
+    shim.includes(includes)
+        .cpp(true)
+        .file("shim/datastream.cpp")
+        .file("shim/mem_image.cpp")
+        .static_flag(true)
+        .shared_flag(false);
+
+    #[cfg(windows)]
+    shim.static_crt(true);
+
+    shim.compile("rawshim");
+
+    println!("cargo:rustc-link-lib=static=rawshim");
+    println!(
+        "cargo:rustc-link-search=native={}",
+        out_dir.as_ref().join("lib").display()
+    );
+
     Ok(())
 }
 
@@ -0,0 +1,144 @@
+#include "libraw.h"
+#include <stdio.h>
+#include <string.h>
+
+// Callbacks implemented in rust (src/datastream.rs)
+//
+// read returns the number of bytes read or -1 on error
+// seek takes an absolute position and returns the new position or -1 on error
+extern "C" {
+typedef INT64 (*libraw_rs_read_fn)(void *data, void *ptr, size_t len);
+typedef INT64 (*libraw_rs_seek_fn)(void *data, INT64 pos);
+
+struct libraw_rs_callbacks {
+  void *data;
+  libraw_rs_read_fn read;
+  libraw_rs_seek_fn seek;
+  INT64 size;
+};
+
+void *libraw_rs_datastream_new(const libraw_rs_callbacks *callbacks);
+void libraw_rs_datastream_delete(void *stream);
+int libraw_rs_open_datastream(libraw_data_t *lr, void *stream);
+}
+
+class LibRaw_rust_datastream : public LibRaw_abstract_datastream {
+public:
+  LibRaw_rust_datastream(const libraw_rs_callbacks &callbacks)
+      : cb(callbacks), pos(0) {}
+  virtual ~LibRaw_rust_datastream() {}
+
+  virtual int valid() { return cb.read != NULL && cb.seek != NULL; }
+
+  virtual int read(void *ptr, size_t size, size_t nmemb) {
+    size_t total = size * nmemb;
+    size_t got = 0;
+    while (got < total) {
+      INT64 r = cb.read(cb.data, (char *)ptr + got, total - got);
+      if (r <= 0)
+        break;
+      got += (size_t)r;
+    }
+    pos += got;
+    return size ? int(got / size) : 0;
+  }
+
+  virtual int seek(INT64 o, int whence) {
+    INT64 target;
+    switch (whence) {
+    case SEEK_SET:
+      target = o;
+      break;
+    case SEEK_CUR:
+      target = pos + o;
+      break;
+    case SEEK_END:
+      target = cb.size + o;
+      break;
+    default:
+      return -1;
+    }
+    if (target < 0)
+      target = 0;
+    if (target > cb.size)
+      target = cb.size;
+    INT64 r = cb.seek(cb.data, target);
+    if (r < 0)
+      return -1;
+    pos = r;
+    return 0;
+  }
+
+  virtual INT64 tell() { return pos; }
+  virtual INT64 size() { return cb.size; }
+
+  virtual int get_char() {
+    unsigned char c;
+    if (read(&c, 1, 1) != 1)
+      return -1;
+    return c;
+  }
+
+  virtual char *gets(char *s, int sz) {
+    if (sz < 1 || pos >= cb.size)
+      return NULL;
+    int i = 0;
+    while (i < sz - 1) {
+      int c = get_char();
+      if (c < 0)
+        break;
+      s[i++] = (char)c;
+      if (c == '\n')
+        break;
+    }
+    s[i] = 0;
+    return i ? s : NULL;
+  }
+
+  // Same semantics as LibRaw_buffer_datastream::scanf_one
+  virtual int scanf_one(const char *fmt, void *val) {
+    if (pos >= cb.size)
+      return -1;
+    char buf[24];
+    int i = 0;
+    int c;
+    // Skip the leading whitespace
+    do {
+      c = get_char();
+    } while (c == ' ' || c == '\t' || c == '\n');
+    while (c >= 0 && c != ' ' && c != '\t' && c != '\n' && c != 0 &&
+           i < (int)sizeof(buf) - 1) {
+      buf[i++] = (char)c;
+      c = get_char();
+    }
+    buf[i] = 0;
+    return i ? sscanf(buf, fmt, val) : -1;
+  }
+
+  virtual int eof() { return pos >= cb.size; }
+
+#ifdef LIBRAW_OLD_VIDEO_SUPPORT
+  virtual void *make_jas_stream() { return NULL; }
+#endif
+
+private:
+  libraw_rs_callbacks cb;
+  INT64 pos;
+};
+
+void *libraw_rs_datastream_new(const libraw_rs_callbacks *callbacks) {
+  return new LibRaw_rust_datastream(*callbacks);
+}
+
+void libraw_rs_datastream_delete(void *stream) {
+  delete (LibRaw_rust_datastream *)stream;
+}
+
+// The datastream is not owned by libraw when opened this way so it has to be deleted by the
+// caller after libraw_recycle / libraw_close
+int libraw_rs_open_datastream(libraw_data_t *lr, void *stream) {
+  if (!lr || !lr->parent_class)
+    return LIBRAW_UNSPECIFIED_ERROR;
+  LibRaw *ip = (LibRaw *)lr->parent_class;
+  return ip->open_datastream((LibRaw_rust_datastream *)stream);
+}
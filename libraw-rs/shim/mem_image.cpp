@@ -0,0 +1,28 @@
+#include "libraw.h"
+
+// get_mem_image_format / copy_mem_image are only part of the C++ api
+extern "C" {
+void libraw_rs_get_mem_image_format(libraw_data_t *lr, int *width, int *height,
+                                    int *colors, int *bps);
+int libraw_rs_copy_mem_image(libraw_data_t *lr, void *scan0, int stride,
+                             int bgr);
+}
+
+void libraw_rs_get_mem_image_format(libraw_data_t *lr, int *width, int *height,
+                                    int *colors, int *bps) {
+  *width = *height = *colors = *bps = 0;
+  if (!lr || !lr->parent_class)
+    return;
+  LibRaw *ip = (LibRaw *)lr->parent_class;
+  ip->get_mem_image_format(width, height, colors, bps);
+}
+
+// scan0 must hold height rows of stride bytes in the format returned by
+// get_mem_image_format
+int libraw_rs_copy_mem_image(libraw_data_t *lr, void *scan0, int stride,
+                             int bgr) {
+  if (!lr || !lr->parent_class)
+    return LIBRAW_UNSPECIFIED_ERROR;
+  LibRaw *ip = (LibRaw *)lr->parent_class;
+  return ip->copy_mem_image(scan0, stride, bgr);
+}
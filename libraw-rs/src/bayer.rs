@@ -0,0 +1,177 @@
+//! Open headless bayer dumps (machine vision / Raspberry Pi sensors) with libraw_open_bayer
+
+use crate::raw::CfaPattern;
+use crate::{Flip, LibrawError, Processor};
+
+/// Describes the layout of a headless bayer frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BayerSpec {
+    /// Width of the whole frame including the margins
+    pub width: u16,
+    /// Height of the whole frame including the margins
+    pub height: u16,
+    pub margins: BayerMargins,
+    /// Bayer pattern of the top left pixel of the frame
+    pub pattern: CfaPattern,
+    /// Bits per pixel in the buffer, one of 8, 10, 12 or 16
+    ///
+    /// 10 bit data can either be loosely packed (6 pixels in 8 bytes) or tightly packed (4 pixels
+    /// in 5 bytes, see [`BayerFlags::tight_10bit`]), 12 bit data is packed (2 pixels in 3 bytes)
+    /// and 16 bit data is stored in one u16 per pixel. Packed rows must not be padded to more
+    /// bits per pixel than `bits` (eg. loose 10 bit needs a width divisible by 6).
+    pub bits: u8,
+    /// Number of bits that are never set at the bottom of the range, used to calculate the
+    /// maximum value
+    pub unused_bits: u32,
+    pub black_level: u32,
+    pub flags: BayerFlags,
+}
+
+/// Number of pixels to crop from each side of the frame
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BayerMargins {
+    pub left: u16,
+    pub top: u16,
+    pub right: u16,
+    pub bottom: u16,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BayerFlags {
+    /// 16 bit data is big endian
+    pub big_endian: bool,
+    /// 10 bit data is tightly packed (4 pixels in 5 bytes, android RAW10)
+    pub tight_10bit: bool,
+    /// Pixels with a value of 0 are dead pixels and should be interpolated
+    pub zero_is_bad: bool,
+    /// Orientation of the frame
+    pub flip: Flip,
+}
+
+impl CfaPattern {
+    /// The bayer pattern as expected by libraw_open_bayer
+    pub fn openbayer_pattern(&self) -> u8 {
+        (match self {
+            CfaPattern::Rggb => sys::LibRaw_openbayer_patterns_LIBRAW_OPENBAYER_RGGB,
+            CfaPattern::Bggr => sys::LibRaw_openbayer_patterns_LIBRAW_OPENBAYER_BGGR,
+            CfaPattern::Grbg => sys::LibRaw_openbayer_patterns_LIBRAW_OPENBAYER_GRBG,
+            CfaPattern::Gbrg => sys::LibRaw_openbayer_patterns_LIBRAW_OPENBAYER_GBRG,
+        }) as u8
+    }
+}
+
+impl BayerSpec {
+    /// Number of bytes in a single row of the frame
+    pub fn row_bytes(&self) -> Option<usize> {
+        let width = self.width as usize;
+        match (self.bits, self.flags.tight_10bit) {
+            (8, _) => Some(width),
+            // android_tight_load_raw reads whole 8 byte words
+            (10, true) => Some((5 * width + 31) / 32 * 8),
+            // android_loose_load_raw reads 6 pixels from every 8 byte word
+            (10, false) => Some((width + 5) / 6 * 8),
+            (12, _) => Some(width * 3 / 2),
+            (16, _) => Some(width * 2),
+            _ => None,
+        }
+    }
+
+    /// Number of bytes in the whole frame
+    pub fn frame_bytes(&self) -> Option<usize> {
+        Some(self.row_bytes()? * self.height as usize)
+    }
+
+    fn validate(&self, len: usize) -> Result<usize, LibrawError> {
+        if self.width == 0 || self.height == 0 {
+            return Err(LibrawError::InvalidBayerSpec(
+                "width and height must be non zero",
+            ));
+        }
+        if self.margins.left as u32 + self.margins.right as u32 >= self.width as u32
+            || self.margins.top as u32 + self.margins.bottom as u32 >= self.height as u32
+        {
+            return Err(LibrawError::InvalidBayerSpec(
+                "margins are larger than the frame",
+            ));
+        }
+        if self.flags.tight_10bit && self.bits != 10 {
+            return Err(LibrawError::InvalidBayerSpec(
+                "tight packing is only supported for 10 bit data",
+            ));
+        }
+        let frame = self.frame_bytes().ok_or(LibrawError::InvalidBayerSpec(
+            "bits must be one of 8, 10, 12 or 16",
+        ))?;
+        // libraw derives the bits per pixel from the length of the frame
+        if frame * 8 / (self.width as usize * self.height as usize) != self.bits as usize {
+            return Err(LibrawError::InvalidBayerSpec(
+                "width doesn't fit the packing of the bits per pixel",
+            ));
+        }
+        if !(0..=7).contains(&self.flags.flip.0) {
+            return Err(LibrawError::InvalidBayerSpec(
+                "flip must be between 0 and 7",
+            ));
+        }
+        if len < frame {
+            return Err(LibrawError::InvalidBayerSpec(
+                "buffer is smaller than the frame",
+            ));
+        }
+        if frame > u32::MAX as usize {
+            return Err(LibrawError::InvalidBayerSpec("frame is too large"));
+        }
+        Ok(frame)
+    }
+}
+
+impl Processor {
+    /// Open a headless bayer frame with libraw_open_bayer
+    ///
+    /// The length of the buffer is checked against the dimensions in `spec` and like
+    /// [`Processor::open_buffer`] the processor keeps the buffer alive until the next open /
+    /// recycle since libraw only reads the pixels in `unpack`.
+    pub fn open_bayer<B>(&mut self, buffer: B, spec: BayerSpec) -> Result<(), LibrawError>
+    where
+        B: AsRef<[u8]> + Send + 'static,
+    {
+        let frame = spec.validate(buffer.as_ref().len())?;
+        self.recycle()?;
+        let buffer = Box::new(buffer);
+        let data = (*buffer).as_ref();
+        let ptr = data.as_ptr() as usize;
+        self.input = Some(buffer);
+
+        let otherflags = match spec.bits {
+            10 => spec.flags.tight_10bit as u32,
+            16 => spec.flags.big_endian as u32,
+            _ => 0,
+        };
+        let procflags = (spec.flags.flip.0 << 2) as u8 | ((spec.flags.zero_is_bad as u8) << 1);
+
+        // libraw picks the decoder from the length of the buffer so only pass the frame
+        let open = move |p: &mut Processor| {
+            LibrawError::check(unsafe {
+                sys::libraw_open_bayer(
+                    p.inner.as_ptr(),
+                    ptr as *mut libc::c_uchar,
+                    frame as libc::c_uint,
+                    spec.width,
+                    spec.height,
+                    spec.margins.left,
+                    spec.margins.top,
+                    spec.margins.right,
+                    spec.margins.bottom,
+                    procflags,
+                    spec.pattern.openbayer_pattern(),
+                    spec.unused_bits,
+                    otherflags,
+                    spec.black_level,
+                )
+            })
+        };
+        open(self)?;
+        self.reopen = Some(Box::new(open));
+        Ok(())
+    }
+}
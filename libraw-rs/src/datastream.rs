@@ -0,0 +1,160 @@
+//! Rust backed LibRaw_abstract_datastream
+//!
+//! The C++ side (`shim/datastream.cpp`) forwards every read / seek libraw does to the callbacks
+//! here which in turn call the [`Read`] + [`Seek`] implementation passed to
+//! [`Processor::open_reader`].
+
+use core::ffi::{c_int, c_void};
+use core::ptr::NonNull;
+use std::io::{Read, Seek, SeekFrom};
+use std::panic::AssertUnwindSafe;
+
+use crate::{LibrawError, Processor};
+
+#[repr(C)]
+struct Callbacks {
+    data: *mut c_void,
+    read: extern "C" fn(data: *mut c_void, ptr: *mut c_void, len: usize) -> i64,
+    seek: extern "C" fn(data: *mut c_void, pos: i64) -> i64,
+    size: i64,
+}
+
+extern "C" {
+    fn libraw_rs_datastream_new(callbacks: *const Callbacks) -> *mut c_void;
+    fn libraw_rs_datastream_delete(stream: *mut c_void);
+    fn libraw_rs_open_datastream(lr: *mut sys::libraw_data_t, stream: *mut c_void) -> c_int;
+}
+
+struct Source<R> {
+    reader: R,
+    /// The last io error from the reader since libraw only sees a short read
+    error: Option<std::io::Error>,
+}
+
+/// Owns the C++ datastream and the reader it reads from
+///
+/// Must be dropped only after libraw_recycle / libraw_close has been called on the processor it
+/// was opened with
+pub(crate) struct ReaderDatastream<R> {
+    stream: NonNull<c_void>,
+    source: NonNull<Source<R>>,
+}
+
+// The stream is only ever used by the processor which owns it
+unsafe impl<R: Send> Send for ReaderDatastream<R> {}
+
+impl<R> Drop for ReaderDatastream<R> {
+    fn drop(&mut self) {
+        unsafe {
+            libraw_rs_datastream_delete(self.stream.as_ptr());
+            drop(Box::from_raw(self.source.as_ptr()));
+        }
+    }
+}
+
+impl<R: Read + Seek> ReaderDatastream<R> {
+    fn new(mut reader: R) -> Result<Self, LibrawError> {
+        let size = reader.seek(SeekFrom::End(0))?;
+        reader.rewind()?;
+        let source = Box::into_raw(Box::new(Source {
+            reader,
+            error: None,
+        }));
+        let callbacks = Callbacks {
+            data: source as *mut c_void,
+            read: Self::read,
+            seek: Self::seek,
+            size: size as i64,
+        };
+        let source = NonNull::new(source).expect("Box is never null");
+        match NonNull::new(unsafe { libraw_rs_datastream_new(&callbacks) }) {
+            Some(stream) => Ok(Self { stream, source }),
+            None => {
+                drop(unsafe { Box::from_raw(source.as_ptr()) });
+                Err(LibrawError::CustomError(
+                    "Failed to create datastream".into(),
+                ))
+            }
+        }
+    }
+
+    /// Takes the io error which caused libraw to fail if any
+    fn take_error(&mut self) -> Option<std::io::Error> {
+        unsafe { (*self.source.as_ptr()).error.take() }
+    }
+
+    extern "C" fn read(data: *mut c_void, ptr: *mut c_void, len: usize) -> i64 {
+        let source = unsafe { &mut *(data as *mut Source<R>) };
+        let buffer = unsafe { std::slice::from_raw_parts_mut(ptr as *mut u8, len) };
+        // Unwinding into C++ is UB so treat a panic in the reader as an io error
+        match std::panic::catch_unwind(AssertUnwindSafe(|| loop {
+            match source.reader.read(buffer) {
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                r => break r,
+            }
+        })) {
+            Ok(Ok(read)) => read as i64,
+            Ok(Err(e)) => {
+                source.error = Some(e);
+                -1
+            }
+            Err(_) => {
+                source.error = Some(std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    "Reader panicked",
+                ));
+                -1
+            }
+        }
+    }
+
+    extern "C" fn seek(data: *mut c_void, pos: i64) -> i64 {
+        let source = unsafe { &mut *(data as *mut Source<R>) };
+        match std::panic::catch_unwind(AssertUnwindSafe(|| {
+            source.reader.seek(SeekFrom::Start(pos as u64))
+        })) {
+            Ok(Ok(pos)) => pos as i64,
+            Ok(Err(e)) => {
+                source.error = Some(e);
+                -1
+            }
+            Err(_) => {
+                source.error = Some(std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    "Reader panicked",
+                ));
+                -1
+            }
+        }
+    }
+}
+
+impl Processor {
+    /// Open a raw file from anything that implements [`Read`] + [`Seek`]
+    ///
+    /// The reader is kept alive by the processor and is used by `unpack` / `unpack_thumb` as
+    /// well, it is dropped on the next open / recycle or when the processor is dropped.
+    ///
+    /// Wrap unbuffered readers in a [`std::io::BufReader`] since libraw does a lot of small reads
+    pub fn open_reader<R>(&mut self, reader: R) -> Result<(), LibrawError>
+    where
+        R: Read + Seek + Send + 'static,
+    {
+        self.recycle()?;
+        let mut stream = ReaderDatastream::new(reader)?;
+        let ret = unsafe { libraw_rs_open_datastream(self.inner.as_ptr(), stream.stream.as_ptr()) };
+        if let Err(e) = LibrawError::check(ret) {
+            // libraw keeps a pointer to the stream even if open failed
+            unsafe { sys::libraw_recycle(self.inner.as_ptr()) };
+            return Err(stream.take_error().map(LibrawError::from).unwrap_or(e));
+        }
+        let ptr = stream.stream.as_ptr() as usize;
+        self.input = Some(Box::new(stream));
+        self.reopen = Some(Box::new(move |p| {
+            LibrawError::check(unsafe {
+                libraw_rs_open_datastream(p.inner.as_ptr(), ptr as *mut c_void)
+            })
+        }));
+        Ok(())
+    }
+}
@@ -29,8 +29,50 @@ impl Processor {
         )
     }
 
+    /// [`Processor::render`] with the current params, with `half_size` for this run only if set
+    ///
+    /// Only `half_size` is touched so params set directly with [`Processor::params`] are kept
+    #[cfg(any(feature = "image", feature = "tiff"))]
+    pub(crate) fn process_image(&mut self, half_size: bool) -> Result<ProcessedImage, LibrawError> {
+        let progress = unsafe { self.inner.as_ref().progress_flags };
+        if progress & sys::LibRaw_progress_LIBRAW_PROGRESS_LOAD_RAW == 0 {
+            self.unpack()?;
+        }
+        let previous = self.params().half_size;
+        if half_size {
+            self.params().half_size = 1;
+        }
+        unsafe { sys::libraw_free_image(self.inner.as_ptr()) };
+        let image = self
+            .dcraw_process()
+            .and_then(|_| self.dcraw_process_make_mem_image());
+        self.params().half_size = previous;
+        image
+    }
+
+    /// Process the unpacked raw with `options` into a new image
+    ///
+    /// dcraw_process rebuilds the working image from the unpacked raw (raw2image) so a single
+    /// `unpack` can be rendered any number of times with different options, eg. a half size
+    /// preview and a full size export. Unpacks first if it hasn't been done yet.
+    ///
+    /// The options stay set on the processor afterwards.
+    pub fn render(&mut self, options: &ProcessingOptions) -> Result<ProcessedImage, LibrawError> {
+        self.set_processing_options(options)?;
+        let progress = unsafe { self.inner.as_ref().progress_flags };
+        if progress & sys::LibRaw_progress_LIBRAW_PROGRESS_LOAD_RAW == 0 {
+            self.unpack()?;
+        }
+        // Also resets the progress flags to right after unpack
+        unsafe { sys::libraw_free_image(self.inner.as_ptr()) };
+        self.dcraw_process()?;
+        self.dcraw_process_make_mem_image()
+    }
+
+    /// Write the processed image to a ppm / tiff file with libraw, see
+    /// [`Processor::write_ppm_tiff`] for other destinations
     pub fn dcraw_ppm_tiff_writer(
-        self,
+        &mut self,
         path: impl AsRef<std::path::Path>,
     ) -> Result<(), LibrawError> {
         LibrawError::check(unsafe {
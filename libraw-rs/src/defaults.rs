@@ -1,31 +1,58 @@
+use crate::options::WhiteBalance;
+use crate::presets::ProcessingPreset;
 use crate::*;
 
-pub fn half_size() -> Processor {
+fn with_options(options: ProcessingOptions) -> Processor {
     Processor::builder()
-        .with_params([Params::HalfSize(true)])
+        .with_options(&options)
+        .expect("Default options are valid")
         .build()
 }
+
+fn half_size_options(white_balance: WhiteBalance) -> ProcessingOptions {
+    ProcessingOptions {
+        half_size: true,
+        white_balance,
+        ..Default::default()
+    }
+}
+
+fn auto_camera_wb_options() -> ProcessingOptions {
+    ProcessingOptions {
+        white_balance: WhiteBalance::CameraOrAuto,
+        ..Default::default()
+    }
+}
+
+/// The options of the functions in this module as named presets
+pub fn presets() -> Vec<ProcessingPreset> {
+    vec![
+        ProcessingPreset::new("half_size", half_size_options(WhiteBalance::Daylight)),
+        ProcessingPreset::new("half_size_auto_wb", half_size_options(WhiteBalance::Auto)),
+        ProcessingPreset::new(
+            "half_size_camera_wb",
+            half_size_options(WhiteBalance::Camera),
+        ),
+        ProcessingPreset::new(
+            "half_size_auto_camera_wb",
+            half_size_options(WhiteBalance::CameraOrAuto),
+        ),
+        ProcessingPreset::new("auto_camera_wb", auto_camera_wb_options()),
+    ]
+}
+
+pub fn half_size() -> Processor {
+    with_options(half_size_options(WhiteBalance::Daylight))
+}
 pub fn half_size_auto_wb() -> Processor {
-    Processor::builder()
-        .with_params([Params::HalfSize(true), Params::UseAutoWb(true)])
-        .build()
+    with_options(half_size_options(WhiteBalance::Auto))
 }
 pub fn half_size_camera_wb() -> Processor {
-    Processor::builder()
-        .with_params([Params::HalfSize(true), Params::UseCameraWb(true)])
-        .build()
+    with_options(half_size_options(WhiteBalance::Camera))
 }
 pub fn half_size_auto_camera_wb() -> Processor {
-    Processor::builder()
-        .with_params([
-            Params::HalfSize(true),
-            Params::UseCameraWb(true),
-            Params::UseAutoWb(true),
-        ])
-        .build()
+    with_options(half_size_options(WhiteBalance::CameraOrAuto))
 }
 pub fn auto_camera_wb() -> Processor {
-    Processor::builder()
-        .with_params([Params::UseCameraWb(true), Params::UseAutoWb(true)])
-        .build()
+    with_options(auto_camera_wb_options())
 }
@@ -0,0 +1,382 @@
+//! Encode the processed image into image files
+//!
+//! Every format is behind its own feature: `jpeg`, `png`, `tiff`, `webp` and `avif`.
+//!
+//! The pixels libraw produces are already rotated by `sizes.flip`, so every format gets upright
+//! pixels. With [`Processor::set_embed_exif`] the EXIF from [`Processor::exif_builder`] is
+//! written with the orientation set to [`Orientation::NONE`] into the formats which can carry it
+//! (jpeg, png, tiff and webp, avif gets none) along with the ICC profile of `output_color`, see
+//! [`crate::icc`].
+
+use std::borrow::Cow;
+use std::io::Write;
+
+use crate::icc::IccProfile;
+use crate::options::BitDepth;
+use crate::{LibrawError, Orientation, ProcessedImage, Processor};
+
+/// Encoded image formats
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OutputFormat {
+    /// 8 bit
+    #[cfg(feature = "jpeg")]
+    Jpeg,
+    /// 8 or 16 bit
+    #[cfg(feature = "png")]
+    Png,
+    /// 8 or 16 bit, uncompressed
+    #[cfg(feature = "tiff")]
+    Tiff,
+    /// 8 bit lossless
+    #[cfg(feature = "webp")]
+    WebP,
+    /// 8 bit
+    #[cfg(feature = "avif")]
+    Avif,
+}
+
+impl OutputFormat {
+    pub fn extension(&self) -> &'static str {
+        match *self {
+            #[cfg(feature = "jpeg")]
+            OutputFormat::Jpeg => "jpg",
+            #[cfg(feature = "png")]
+            OutputFormat::Png => "png",
+            #[cfg(feature = "tiff")]
+            OutputFormat::Tiff => "tif",
+            #[cfg(feature = "webp")]
+            OutputFormat::WebP => "webp",
+            #[cfg(feature = "avif")]
+            OutputFormat::Avif => "avif",
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match *self {
+            #[cfg(feature = "jpeg")]
+            OutputFormat::Jpeg => "image/jpeg",
+            #[cfg(feature = "png")]
+            OutputFormat::Png => "image/png",
+            #[cfg(feature = "tiff")]
+            OutputFormat::Tiff => "image/tiff",
+            #[cfg(feature = "webp")]
+            OutputFormat::WebP => "image/webp",
+            #[cfg(feature = "avif")]
+            OutputFormat::Avif => "image/avif",
+        }
+    }
+
+    /// Whether 16 bit samples are written as is
+    pub fn supports_16_bit(&self) -> bool {
+        match *self {
+            #[cfg(feature = "png")]
+            OutputFormat::Png => true,
+            #[cfg(feature = "tiff")]
+            OutputFormat::Tiff => true,
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
+    }
+}
+
+/// Options of [`Processor::encode`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncodeOptions {
+    /// Quality of the lossy formats (jpeg, avif), 1 - 100
+    pub quality: u8,
+    /// Bits per sample, [`None`] keeps the depth of the processed image (`output_bps`)
+    ///
+    /// Formats without 16 bit support always get 8 bits.
+    pub bit_depth: Option<BitDepth>,
+    /// ICC profile to embed, [`None`] uses [`Processor::icc_profile`]
+    ///
+    /// Avif can't carry a profile, encoding it fails if the profile resolves to anything but
+    /// sRGB (or nothing).
+    pub icc_profile: Option<IccProfile>,
+    /// Speed of the avif encoder, 1 (slowest, smallest) - 10
+    pub avif_speed: u8,
+    /// Resize before encoding, processing with half_size when possible
+    #[cfg(feature = "image")]
+    pub resize: Option<crate::resize::Resize>,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        Self {
+            quality: 90,
+            bit_depth: None,
+            icc_profile: None,
+            avif_speed: 4,
+            #[cfg(feature = "image")]
+            resize: None,
+        }
+    }
+}
+
+impl EncodeOptions {
+    pub fn validate(&self) -> Result<(), LibrawError> {
+        if !(1..=100).contains(&self.quality) {
+            return Err(LibrawError::InvalidOption(
+                "quality must be between 1 and 100",
+            ));
+        }
+        if !(1..=10).contains(&self.avif_speed) {
+            return Err(LibrawError::InvalidOption(
+                "avif speed must be between 1 and 10",
+            ));
+        }
+        #[cfg(feature = "image")]
+        if let Some(resize) = &self.resize {
+            resize.validate()?;
+        }
+        Ok(())
+    }
+}
+
+/// Samples of the image to encode
+enum Data<'a> {
+    Eight(Cow<'a, [u8]>),
+    Sixteen(Cow<'a, [u16]>),
+}
+
+/// Gray or rgb pixels to encode
+struct Pixels<'a> {
+    width: u32,
+    height: u32,
+    colors: u16,
+    data: Data<'a>,
+}
+
+impl<'a> Pixels<'a> {
+    fn from_image(image: &'a ProcessedImage) -> Result<Self, LibrawError> {
+        if !matches!(image.colors(), 1 | 3) {
+            return Err(LibrawError::InvalidImageLayout(
+                "only gray and rgb images can be encoded",
+            ));
+        }
+        let data = match image.bits() {
+            8 => Data::Eight(Cow::Borrowed(image.samples(8, None)?)),
+            16 => Data::Sixteen(Cow::Borrowed(image.samples(16, None)?)),
+            bits => return Err(LibrawError::InvalidColor(bits)),
+        };
+        Ok(Self {
+            width: image.width(),
+            height: image.height(),
+            colors: image.colors(),
+            data,
+        })
+    }
+
+    #[cfg(feature = "image")]
+    fn from_dynamic(image: image::DynamicImage) -> Self {
+        use image::DynamicImage::*;
+        let (width, height) = (image.width(), image.height());
+        let (colors, data) = match image {
+            ImageLuma8(image) => (1, Data::Eight(image.into_raw().into())),
+            ImageRgb8(image) => (3, Data::Eight(image.into_raw().into())),
+            ImageLuma16(image) => (1, Data::Sixteen(image.into_raw().into())),
+            ImageRgb16(image) => (3, Data::Sixteen(image.into_raw().into())),
+            // Processed images don't have alpha or float samples
+            image => (3, Data::Sixteen(image.into_rgb16().into_raw().into())),
+        };
+        Self {
+            width,
+            height,
+            colors,
+            data,
+        }
+    }
+
+    /// Convert to the bit depth written for `format`
+    fn with_bit_depth(self, format: OutputFormat, bit_depth: Option<BitDepth>) -> Self {
+        let sixteen = match bit_depth {
+            Some(bits) => bits == BitDepth::Sixteen,
+            None => matches!(self.data, Data::Sixteen(_)),
+        } && format.supports_16_bit();
+        let data = match (self.data, sixteen) {
+            (Data::Eight(samples), true) => {
+                Data::Sixteen(samples.iter().map(|s| *s as u16 * 257).collect())
+            }
+            (Data::Sixteen(samples), false) => {
+                Data::Eight(samples.iter().map(|s| (s >> 8) as u8).collect())
+            }
+            (data, _) => data,
+        };
+        Self { data, ..self }
+    }
+
+    /// Native endian bytes for the `image` encoders
+    #[cfg(feature = "image")]
+    fn bytes(&self) -> &[u8] {
+        match &self.data {
+            Data::Eight(samples) => samples,
+            Data::Sixteen(samples) => unsafe {
+                std::slice::from_raw_parts(samples.as_ptr() as *const u8, samples.len() * 2)
+            },
+        }
+    }
+
+    #[cfg(feature = "image")]
+    fn color_type(&self) -> image::ColorType {
+        match (&self.data, self.colors) {
+            (Data::Eight(_), 1) => image::ColorType::L8,
+            (Data::Eight(_), _) => image::ColorType::Rgb8,
+            (Data::Sixteen(_), 1) => image::ColorType::L16,
+            (Data::Sixteen(_), _) => image::ColorType::Rgb16,
+        }
+    }
+}
+
+impl Processor {
+    /// Process the raw (unpacking it first if needed) and encode it as `format`
+    pub fn encode(
+        &mut self,
+        format: OutputFormat,
+        options: &EncodeOptions,
+    ) -> Result<Vec<u8>, LibrawError> {
+        let mut out = Vec::new();
+        self.encode_to(&mut out, format, options)?;
+        Ok(out)
+    }
+
+    /// [`Processor::encode`] into `writer`
+    pub fn encode_to<W: Write>(
+        &mut self,
+        writer: W,
+        format: OutputFormat,
+        options: &EncodeOptions,
+    ) -> Result<(), LibrawError> {
+        options.validate()?;
+        #[cfg(feature = "image")]
+        let half_size = options
+            .resize
+            .as_ref()
+            .map_or(false, |resize| self.wants_half_size(resize));
+        #[cfg(not(feature = "image"))]
+        let half_size = false;
+        let image = self.process_image(half_size)?;
+        self.encode_image_to(&image, writer, format, options)
+    }
+
+    /// Encode an already processed image (eg. from [`Processor::render`]) with the metadata of
+    /// the opened file
+    pub fn encode_image_to<W: Write>(
+        &self,
+        image: &ProcessedImage,
+        mut writer: W,
+        format: OutputFormat,
+        options: &EncodeOptions,
+    ) -> Result<(), LibrawError> {
+        options.validate()?;
+        #[cfg(feature = "image")]
+        let pixels = match &options.resize {
+            Some(resize) => Pixels::from_dynamic(resize.apply(image.to_dynamic_image()?)),
+            None => Pixels::from_image(image)?,
+        };
+        #[cfg(not(feature = "image"))]
+        let pixels = Pixels::from_image(image)?;
+        let pixels = pixels.with_bit_depth(format, options.bit_depth);
+        let exif = self
+            .embed_exif
+            .then(|| self.exif_builder().orientation(Orientation::NONE));
+        let profile = options.icc_profile.as_ref().unwrap_or(&self.icc_profile);
+        let icc = self.icc_profile_for(profile, pixels.colors)?;
+        let icc = icc.as_deref();
+        let (width, height) = (pixels.width, pixels.height);
+        match format {
+            #[cfg(feature = "jpeg")]
+            OutputFormat::Jpeg => {
+                let mut jpeg = Vec::new();
+                image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg, options.quality)
+                    .encode(pixels.bytes(), width, height, pixels.color_type())?;
+                if let Some(exif) = exif {
+                    jpeg = crate::orientation::set_jpeg_exif(jpeg, &exif.build(), false)?;
+                }
+                if let Some(icc) = icc {
+                    jpeg = crate::icc::set_jpeg_icc(jpeg, icc)?;
+                }
+                writer.write_all(&jpeg)?;
+            }
+            #[cfg(feature = "png")]
+            OutputFormat::Png => {
+                use image::ImageEncoder;
+                use img_parts::{ImageEXIF, ImageICC};
+                let mut png = Vec::new();
+                image::codecs::png::PngEncoder::new(&mut png).write_image(
+                    pixels.bytes(),
+                    width,
+                    height,
+                    pixels.color_type(),
+                )?;
+                let mut parts = img_parts::png::Png::from_bytes(png.into())?;
+                parts.set_exif(exif.map(|exif| exif.build().into()));
+                parts.set_icc_profile(icc.map(|icc| icc.to_vec().into()));
+                parts.encoder().write_to(&mut writer)?;
+            }
+            #[cfg(feature = "tiff")]
+            OutputFormat::Tiff => {
+                use crate::tiff::{Ifd, Samples, Value, INTER_COLOR_PROFILE};
+                let (mut ifd0, sub_ifds) = match exif {
+                    Some(exif) => exif.ifds(),
+                    None => (Ifd::new(), Vec::new()),
+                };
+                if let Some(icc) = icc {
+                    ifd0.insert(INTER_COLOR_PROFILE, Value::Undefined(icc.to_vec()));
+                }
+                let samples = match &pixels.data {
+                    Data::Eight(samples) => Samples::U8(samples),
+                    Data::Sixteen(samples) => Samples::U16(samples),
+                };
+                crate::tiff::write_image(
+                    &mut writer,
+                    width,
+                    height,
+                    pixels.colors,
+                    samples,
+                    ifd0,
+                    &sub_ifds,
+                )?;
+            }
+            #[cfg(feature = "webp")]
+            OutputFormat::WebP => {
+                use image::ImageEncoder;
+                use img_parts::{ImageEXIF, ImageICC};
+                let mut webp = Vec::new();
+                image::codecs::webp::WebPEncoder::new_lossless(&mut webp).write_image(
+                    pixels.bytes(),
+                    width,
+                    height,
+                    pixels.color_type(),
+                )?;
+                let mut parts = img_parts::webp::WebP::from_bytes(webp.into())?;
+                parts.set_exif(exif.map(|exif| exif.build().into()));
+                parts.set_icc_profile(icc.map(|icc| icc.to_vec().into()));
+                parts.encoder().write_to(&mut writer)?;
+            }
+            #[cfg(feature = "avif")]
+            OutputFormat::Avif => {
+                use crate::options::{Gamma, OutputColorSpace};
+                use image::ImageEncoder;
+                // ravif can't write EXIF or ICC profiles, the pixels are read as sRGB
+                let srgb = [Gamma::Bt709, Gamma::Srgb]
+                    .map(|gamma| crate::icc::output_profile(OutputColorSpace::Srgb, gamma));
+                if let Some(icc) = icc {
+                    if !srgb.iter().any(|profile| profile.as_deref() == Some(icc)) {
+                        return Err(LibrawError::InvalidOption(
+                            "avif can't embed an icc profile, the output has to be srgb",
+                        ));
+                    }
+                }
+                let _ = exif;
+                image::codecs::avif::AvifEncoder::new_with_speed_quality(
+                    &mut writer,
+                    options.avif_speed,
+                    options.quality,
+                )
+                .write_image(pixels.bytes(), width, height, pixels.color_type())?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -11,21 +11,44 @@ pub enum LibrawError {
     #[cfg(windows)]
     #[error("{0}")]
     WidestringError(#[from] widestring::error::NulError<u16>),
-    #[cfg(feature = "jpeg")]
+    #[cfg(feature = "image")]
     #[error("{0}")]
     ImageError(#[from] image::error::ImageError),
     #[error("Unsupported Thumbnail")]
     UnsupportedThumbnail,
     #[error("Invalid Number of bits ({0}) for colortype")]
     InvalidColor(u16),
-    #[cfg(feature = "jpeg")]
+    #[cfg(any(feature = "jpeg", feature = "png", feature = "webp"))]
     #[error("{0}")]
     ImgPartsError(#[from] img_parts::Error),
-    #[cfg(feature = "jpeg")]
+    #[cfg(feature = "image")]
     #[error("Failed to encode the processed image into and rgb image")]
     EncodingError,
     #[error("Missing XMP header in raw file")]
     XMPMissing,
+    #[error("No raw data available, unpack has not been called or the layout is not supported")]
+    RawDataMissing,
+    #[error("Invalid bayer spec: {0}")]
+    InvalidBayerSpec(&'static str),
+    #[error("Invalid thumbnail: {0}")]
+    InvalidThumbnail(&'static str),
+    #[error("Invalid processing option: {0}")]
+    InvalidOption(&'static str),
+    #[error("Invalid output buffer: {0}")]
+    InvalidOutputBuffer(&'static str),
+    #[error("Invalid processed image: {0}")]
+    InvalidImageLayout(&'static str),
+    #[error("Invalid preset: {0}")]
+    InvalidPreset(String),
+    #[cfg(feature = "toml")]
+    #[error("{0}")]
+    TomlDeError(#[from] toml::de::Error),
+    #[cfg(feature = "toml")]
+    #[error("{0}")]
+    TomlSerError(#[from] toml::ser::Error),
+    #[cfg(feature = "json")]
+    #[error("{0}")]
+    JsonError(#[from] serde_json::Error),
     #[error("{0}")]
     CustomError(#[from] Box<dyn std::error::Error + Send + Sync>),
 }
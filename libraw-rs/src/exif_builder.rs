@@ -0,0 +1,263 @@
+//! Generate EXIF for the images encoded from a raw file
+//!
+//! The jpegs / bitmaps libraw produces don't carry any of the metadata of the raw file, so
+//! [`ExifBuilder`] writes it back from a [`Metadata`] snapshot.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::metadata::{GpsInfo, Metadata};
+use crate::tiff::{link_ifds, rational, Ifd, Value, HEADER};
+use crate::{Orientation, Processor};
+
+const IMAGE_DESCRIPTION: u16 = 0x010e;
+const MAKE: u16 = 0x010f;
+const MODEL: u16 = 0x0110;
+const ORIENTATION: u16 = 0x0112;
+const SOFTWARE: u16 = 0x0131;
+const DATE_TIME: u16 = 0x0132;
+const ARTIST: u16 = 0x013b;
+const EXIF_IFD: u16 = 0x8769;
+const GPS_IFD: u16 = 0x8825;
+
+const EXPOSURE_TIME: u16 = 0x829a;
+const F_NUMBER: u16 = 0x829d;
+const ISO: u16 = 0x8827;
+const EXIF_VERSION: u16 = 0x9000;
+const DATE_TIME_ORIGINAL: u16 = 0x9003;
+const DATE_TIME_DIGITIZED: u16 = 0x9004;
+const OFFSET_TIME: u16 = 0x9010;
+const OFFSET_TIME_ORIGINAL: u16 = 0x9011;
+const OFFSET_TIME_DIGITIZED: u16 = 0x9012;
+const MAX_APERTURE: u16 = 0x9205;
+const FLASH: u16 = 0x9209;
+const FOCAL_LENGTH: u16 = 0x920a;
+const FOCAL_LENGTH_35MM: u16 = 0xa405;
+const BODY_SERIAL: u16 = 0xa431;
+const LENS_SPECIFICATION: u16 = 0xa432;
+const LENS_MAKE: u16 = 0xa433;
+const LENS_MODEL: u16 = 0xa434;
+const LENS_SERIAL: u16 = 0xa435;
+
+/// Builds an EXIF tiff structure from [`Metadata`]
+#[derive(Debug, Clone)]
+pub struct ExifBuilder {
+    metadata: Metadata,
+    orientation: Option<u8>,
+}
+
+impl ExifBuilder {
+    pub fn new(metadata: Metadata) -> Self {
+        Self {
+            metadata,
+            orientation: None,
+        }
+    }
+
+    /// Write the orientation tag, it is left out by default
+    pub fn orientation(mut self, orientation: Orientation) -> Self {
+        self.orientation = Some(orientation.0);
+        self
+    }
+
+    /// Don't write the orientation tag
+    pub fn without_orientation(mut self) -> Self {
+        self.orientation = None;
+        self
+    }
+
+    /// The EXIF as a big endian tiff structure (the contents of an APP1 segment without the
+    /// `Exif\0\0` prefix)
+    pub fn build(&self) -> Vec<u8> {
+        let (mut ifd0, sub_ifds) = self.ifds();
+        link_ifds(&mut ifd0, &sub_ifds, HEADER.len());
+        let mut out = HEADER.to_vec();
+        ifd0.write(&mut out, 0);
+        for (_, ifd) in &sub_ifds {
+            ifd.write(&mut out, 0);
+        }
+        out
+    }
+
+    /// IFD0 and the EXIF / GPS IFDs with the tags pointing at them
+    pub(crate) fn ifds(&self) -> (Ifd, Vec<(u16, Ifd)>) {
+        let camera = &self.metadata.camera;
+        let capture = &self.metadata.capture;
+        let lens = &self.metadata.lens;
+
+        let mut ifd0 = Ifd::new();
+        let ascii = |ifd: &mut Ifd, tag, value: Option<&String>| {
+            if let Some(value) = value.filter(|v| !v.is_empty()) {
+                ifd.insert(tag, Value::Ascii(value.clone()));
+            }
+        };
+        ascii(&mut ifd0, IMAGE_DESCRIPTION, capture.description.as_ref());
+        ascii(&mut ifd0, MAKE, Some(&camera.make));
+        ascii(&mut ifd0, MODEL, Some(&camera.model));
+        ascii(&mut ifd0, SOFTWARE, camera.software.as_ref());
+        ascii(&mut ifd0, ARTIST, capture.artist.as_ref());
+        if let Some(orientation) = self.orientation {
+            ifd0.insert(ORIENTATION, Value::Short(vec![orientation as u16]));
+        }
+        let timestamp = capture.timestamp.and_then(exif_datetime);
+        ascii(&mut ifd0, DATE_TIME, timestamp.as_ref());
+
+        let mut exif = Ifd::new();
+        exif.insert(EXIF_VERSION, Value::Undefined(b"0232".to_vec()));
+        ascii(&mut exif, DATE_TIME_ORIGINAL, timestamp.as_ref());
+        ascii(&mut exif, DATE_TIME_DIGITIZED, timestamp.as_ref());
+        if timestamp.is_some() {
+            // exif_datetime writes UTC
+            let utc = String::from("+00:00");
+            for tag in [OFFSET_TIME, OFFSET_TIME_ORIGINAL, OFFSET_TIME_DIGITIZED] {
+                exif.insert(tag, Value::Ascii(utc.clone()));
+            }
+        }
+        if let Some(shutter) = capture.shutter {
+            exif.insert(
+                EXPOSURE_TIME,
+                Value::Rational(vec![exposure_time(shutter.as_secs_f64())]),
+            );
+        }
+        if let Some(aperture) = capture.aperture {
+            exif.insert(F_NUMBER, Value::Rational(vec![rational(aperture as f64)]));
+        }
+        if let Some(iso) = capture.iso {
+            exif.insert(
+                ISO,
+                Value::Short(vec![iso.round().clamp(0.0, 65535.0) as u16]),
+            );
+        }
+        if let Some(focal_length) = capture.focal_length {
+            exif.insert(
+                FOCAL_LENGTH,
+                Value::Rational(vec![rational(focal_length as f64)]),
+            );
+        }
+        exif.insert(FLASH, Value::Short(vec![capture.flash_used as u16]));
+        ascii(&mut exif, BODY_SERIAL, camera.body_serial.as_ref());
+
+        if let Some(aperture) = lens.exif_max_aperture {
+            // APEX value
+            let apex = 2.0 * (aperture as f64).log2();
+            exif.insert(MAX_APERTURE, Value::Rational(vec![rational(apex)]));
+        }
+        if let Some(focal_length) = lens.focal_length_in_35mm {
+            exif.insert(FOCAL_LENGTH_35MM, Value::Short(vec![focal_length]));
+        }
+        if lens.min_focal.is_some() || lens.max_focal.is_some() {
+            // 0/0 marks unknown values
+            let value = |v: Option<f32>| v.map_or((0, 0), |v| rational(v as f64));
+            exif.insert(
+                LENS_SPECIFICATION,
+                Value::Rational(vec![
+                    value(lens.min_focal),
+                    value(lens.max_focal.or(lens.min_focal)),
+                    value(lens.max_aperture_at_min_focal),
+                    value(lens.max_aperture_at_max_focal),
+                ]),
+            );
+        }
+        ascii(&mut exif, LENS_MAKE, lens.make.as_ref());
+        ascii(&mut exif, LENS_MODEL, lens.model.as_ref());
+        ascii(&mut exif, LENS_SERIAL, lens.serial.as_ref());
+
+        let mut sub_ifds = vec![(EXIF_IFD, exif)];
+        if let Some(gps) = capture.gps.as_ref() {
+            sub_ifds.push((GPS_IFD, gps_ifd(gps)));
+        }
+        (ifd0, sub_ifds)
+    }
+
+    /// Replace the EXIF of a jpeg with the generated one, the XMP packet is kept
+    #[cfg(feature = "jpeg")]
+    pub fn attach_to(&self, jpeg: Vec<u8>) -> Result<Vec<u8>, crate::LibrawError> {
+        crate::orientation::set_jpeg_exif(jpeg, &self.build(), false)
+    }
+}
+
+fn gps_ifd(gps: &GpsInfo) -> Ifd {
+    let mut ifd = Ifd::new();
+    let dms = |v: [f32; 3]| Value::Rational(v.iter().map(|v| rational(*v as f64)).collect());
+    ifd.insert(0x0000, Value::Byte(vec![2, 3, 0, 0]));
+    if gps.latitude_ref.is_ascii_alphabetic() {
+        ifd.insert(0x0001, Value::Ascii(gps.latitude_ref.to_string()));
+        ifd.insert(0x0002, dms(gps.latitude));
+    }
+    if gps.longitude_ref.is_ascii_alphabetic() {
+        ifd.insert(0x0003, Value::Ascii(gps.longitude_ref.to_string()));
+        ifd.insert(0x0004, dms(gps.longitude));
+    }
+    ifd.insert(0x0005, Value::Byte(vec![gps.below_sea_level as u8]));
+    ifd.insert(
+        0x0006,
+        Value::Rational(vec![rational(gps.altitude.abs() as f64)]),
+    );
+    ifd.insert(0x0007, dms(gps.timestamp));
+    if gps.status.is_ascii_alphabetic() {
+        ifd.insert(0x0009, Value::Ascii(gps.status.to_string()));
+    }
+    ifd
+}
+
+/// Shutter speeds below a second are written as 1/x
+fn exposure_time(seconds: f64) -> (u32, u32) {
+    if seconds > 0.0 && seconds < 1.0 {
+        let inverse = 1.0 / seconds;
+        if (inverse - inverse.round()).abs() < 0.01 {
+            return (1, inverse.round() as u32);
+        }
+    }
+    rational(seconds)
+}
+
+/// Format a timestamp as `YYYY:MM:DD HH:MM:SS` in UTC
+///
+/// libraw creates the timestamp with mktime from the local date in the file, converting it back
+/// to local time would make the output depend on the time zone of the machine, so the tags get
+/// UTC along with a `+00:00` offset.
+fn exif_datetime(time: SystemTime) -> Option<String> {
+    let seconds = time.duration_since(UNIX_EPOCH).ok()?.as_secs() as i64;
+    // Days to civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let days = seconds.div_euclid(86400);
+    let time = seconds.rem_euclid(86400);
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    Some(format!(
+        "{:04}:{:02}:{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    ))
+}
+
+impl Processor {
+    /// An [`ExifBuilder`] for the metadata of the opened file with its orientation
+    pub fn exif_builder(&self) -> ExifBuilder {
+        let metadata = self.metadata();
+        let orientation = metadata.sizes.orientation();
+        ExifBuilder::new(metadata).orientation(orientation)
+    }
+
+    /// Whether the images encoded by the processor get the EXIF from [`Processor::exif_builder`]
+    pub fn embed_exif(&self) -> bool {
+        self.embed_exif
+    }
+
+    /// Write the metadata of the raw file as EXIF into every image encoded by the processor
+    /// (enabled by default)
+    ///
+    /// Embedded previews which already contain EXIF are left as they are.
+    pub fn set_embed_exif(&mut self, embed: bool) {
+        self.embed_exif = embed;
+    }
+}
@@ -0,0 +1,138 @@
+//! Multi-shot / multi-frame raw files
+//!
+//! Pixel shift files (Pentax, Sony), Fuji multi exposure files and DNGs with several sub images
+//! contain more than one raw frame. libraw picks the frame with `rawparams.shot_select` while
+//! opening the file, so switching to another frame means opening the file again. The functions
+//! here select the frame and reopen the current file with [`Processor::reopen`].
+
+use crate::error::InternalLibrawError;
+use crate::raw::RawImage;
+use crate::{LibrawError, ProcessedImage, Processor};
+
+impl Processor {
+    /// Number of raw frames in the opened file (idata.raw_count)
+    pub fn frame_count(&self) -> u32 {
+        self.idata().raw_count
+    }
+
+    /// The frame which libraw decodes (rawparams.shot_select)
+    pub fn selected_frame(&self) -> u32 {
+        unsafe { self.inner.as_ref().rawparams.shot_select }
+    }
+
+    /// Select the frame libraw decodes on the next open (rawparams.shot_select)
+    ///
+    /// The value is kept across open / recycle, it also replaces the shot_select of the options set
+    /// with [`Processor::set_raw_options`]
+    pub fn select_frame(&mut self, frame: u32) {
+        if let Some(applied) = &mut self.raw_options {
+            applied.select_shot(frame);
+        }
+        unsafe { self.inner.as_mut().rawparams.shot_select = frame }
+    }
+
+    /// Select `frame`, reopen the current file and unpack it
+    ///
+    /// Returns [`InternalLibrawError::RequestForNonexistentImage`] if the file has fewer frames.
+    /// The previously selected frame is selected again afterwards so later opens aren't affected.
+    pub fn unpack_frame(&mut self, frame: u32) -> Result<(), LibrawError> {
+        self.with_frame(frame, |_| Ok(()))
+    }
+
+    /// Unpack `frame` like [`Processor::unpack_frame`] and call `f` before selecting the
+    /// previous frame again
+    fn with_frame<C, T>(&mut self, frame: u32, f: C) -> Result<T, LibrawError>
+    where
+        C: FnOnce(&mut Processor) -> Result<T, LibrawError>,
+    {
+        let previous = self.selected_frame();
+        self.select_frame(frame);
+        let result = self
+            .reopen()
+            .and_then(|()| {
+                if frame >= self.frame_count().max(1) {
+                    return Err(InternalLibrawError::RequestForNonexistentImage.into());
+                }
+                self.unpack()
+            })
+            .and_then(|()| f(self));
+        self.select_frame(previous);
+        result
+    }
+
+    /// Iterate over the processed images of every frame in the file
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), libraw_r::LibrawError> {
+    /// let mut processor = libraw_r::Processor::default();
+    /// processor.open("pixelshift.pef")?;
+    /// for frame in processor.frames() {
+    ///     let frame = frame?;
+    ///     println!("{}x{}", frame.width(), frame.height());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn frames(&mut self) -> Frames<'_> {
+        Frames {
+            processor: self,
+            next: 0,
+            count: None,
+        }
+    }
+
+    /// Call `f` with the bayer data of every frame in the file
+    ///
+    /// Since [`RawImage`] borrows the processor this can't be an iterator
+    pub fn for_each_raw_frame<C>(&mut self, mut f: C) -> Result<(), LibrawError>
+    where
+        C: FnMut(u32, RawImage<'_>) -> Result<(), LibrawError>,
+    {
+        let mut frame = 0;
+        loop {
+            self.with_frame(frame, |p| f(frame, p.raw_image()?))?;
+            frame += 1;
+            if frame >= self.frame_count() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Iterator over the processed frames of a multi frame file, see [`Processor::frames`]
+pub struct Frames<'p> {
+    processor: &'p mut Processor,
+    next: u32,
+    /// Known after the first frame has been opened
+    count: Option<u32>,
+}
+
+impl<'p> Frames<'p> {
+    fn process(&mut self, frame: u32) -> Result<ProcessedImage, LibrawError> {
+        self.processor.with_frame(frame, |p| {
+            p.dcraw_process()?;
+            p.dcraw_process_make_mem_image()
+        })
+    }
+}
+
+impl<'p> Iterator for Frames<'p> {
+    type Item = Result<ProcessedImage, LibrawError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.count.map_or(false, |count| self.next >= count) {
+            return None;
+        }
+        let frame = self.next;
+        self.next += 1;
+        let image = self.process(frame);
+        match image {
+            Ok(_) => {
+                self.count = Some(self.processor.frame_count().max(1));
+            }
+            // Stop after the first error
+            Err(_) => self.count = Some(0),
+        }
+        Some(image)
+    }
+}
@@ -0,0 +1,300 @@
+//! Minimal HEIF (ISO/IEC 23008-12) writer for the H265 previews of Canon CR3 files
+//!
+//! libraw hands out the bare HEVC bitstream which most decoders can't open on its own, so it is
+//! wrapped in a single image HEIF container (ftyp, meta with an hvcC + ispe property and mdat).
+
+use crate::LibrawError;
+
+const NAL_VPS: u8 = 32;
+const NAL_SPS: u8 = 33;
+const NAL_PPS: u8 = 34;
+
+/// Wrap an HEVC bitstream (annex B or 4 byte length prefixed) in a HEIF container
+pub fn wrap_hevc(bitstream: &[u8], width: u32, height: u32) -> Result<Vec<u8>, LibrawError> {
+    let nals = split_nals(bitstream);
+    let find = |kind: u8| nals.iter().copied().filter(move |n| nal_type(n) == kind);
+    let sps = find(NAL_SPS)
+        .next()
+        .ok_or(LibrawError::InvalidThumbnail("H265 preview without a SPS"))?;
+    let sps = Sps::parse(sps).ok_or(LibrawError::InvalidThumbnail("Malformed H265 SPS"))?;
+
+    // Parameter sets go into hvcC, everything else into mdat
+    let mut data = Vec::with_capacity(bitstream.len() + 4 * nals.len());
+    for nal in nals
+        .iter()
+        .filter(|n| !matches!(nal_type(n), NAL_VPS | NAL_SPS | NAL_PPS))
+    {
+        data.extend_from_slice(&(nal.len() as u32).to_be_bytes());
+        data.extend_from_slice(nal);
+    }
+
+    let mut hvcc = Vec::new();
+    hvcc.push(1);
+    hvcc.extend_from_slice(&sps.profile_tier_level);
+    hvcc.extend_from_slice(&0xf000u16.to_be_bytes());
+    hvcc.push(0xfc);
+    hvcc.push(0xfc | sps.chroma_format_idc);
+    hvcc.push(0xf8 | sps.bit_depth_luma_minus8);
+    hvcc.push(0xf8 | sps.bit_depth_chroma_minus8);
+    hvcc.extend_from_slice(&0u16.to_be_bytes());
+    // constantFrameRate = 0, numTemporalLayers, temporalIdNested, lengthSizeMinusOne = 3
+    hvcc.push(((sps.max_sub_layers & 7) << 3) | ((sps.temporal_id_nesting as u8) << 2) | 3);
+    let arrays = [NAL_VPS, NAL_SPS, NAL_PPS];
+    hvcc.push(arrays.len() as u8);
+    for kind in arrays {
+        let units = find(kind).collect::<Vec<_>>();
+        hvcc.push(0x80 | kind);
+        hvcc.extend_from_slice(&(units.len() as u16).to_be_bytes());
+        for unit in units {
+            hvcc.extend_from_slice(&(unit.len() as u16).to_be_bytes());
+            hvcc.extend_from_slice(unit);
+        }
+    }
+
+    let mut ftyp = Vec::new();
+    ftyp.extend_from_slice(b"heic");
+    ftyp.extend_from_slice(&0u32.to_be_bytes());
+    ftyp.extend_from_slice(b"mif1heic");
+    let ftyp = boxed(b"ftyp", &ftyp);
+
+    // The size of meta doesn't depend on the offset so build it once to get the offset of mdat
+    let meta_len = meta(0, data.len() as u32, &hvcc, width, height).len();
+    let offset = (ftyp.len() + meta_len + 8) as u32;
+
+    let mut heif = ftyp;
+    heif.extend_from_slice(&meta(offset, data.len() as u32, &hvcc, width, height));
+    heif.extend_from_slice(&boxed(b"mdat", &data));
+    Ok(heif)
+}
+
+fn meta(offset: u32, length: u32, hvcc: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let mut hdlr = Vec::new();
+    hdlr.extend_from_slice(&0u32.to_be_bytes());
+    hdlr.extend_from_slice(b"pict");
+    hdlr.extend_from_slice(&[0; 12]);
+    hdlr.push(0);
+
+    let pitm = 1u16.to_be_bytes();
+
+    let mut iloc = Vec::new();
+    // offset_size = 4, length_size = 4, base_offset_size = 0
+    iloc.extend_from_slice(&[0x44, 0x00]);
+    iloc.extend_from_slice(&1u16.to_be_bytes());
+    iloc.extend_from_slice(&1u16.to_be_bytes());
+    iloc.extend_from_slice(&0u16.to_be_bytes());
+    iloc.extend_from_slice(&1u16.to_be_bytes());
+    iloc.extend_from_slice(&offset.to_be_bytes());
+    iloc.extend_from_slice(&length.to_be_bytes());
+
+    let mut infe = Vec::new();
+    infe.extend_from_slice(&1u16.to_be_bytes());
+    infe.extend_from_slice(&0u16.to_be_bytes());
+    infe.extend_from_slice(b"hvc1");
+    infe.push(0);
+    let mut iinf = 1u16.to_be_bytes().to_vec();
+    iinf.extend_from_slice(&full_box(b"infe", 2, &infe));
+
+    let mut ispe = width.to_be_bytes().to_vec();
+    ispe.extend_from_slice(&height.to_be_bytes());
+    let mut ipco = boxed(b"hvcC", hvcc);
+    ipco.extend_from_slice(&full_box(b"ispe", 0, &ispe));
+
+    let mut ipma = 1u32.to_be_bytes().to_vec();
+    ipma.extend_from_slice(&1u16.to_be_bytes());
+    // hvcC is essential, ispe is not
+    ipma.extend_from_slice(&[2, 0x81, 0x02]);
+
+    let mut iprp = boxed(b"ipco", &ipco);
+    iprp.extend_from_slice(&full_box(b"ipma", 0, &ipma));
+
+    let mut meta = full_box(b"hdlr", 0, &hdlr);
+    meta.extend_from_slice(&full_box(b"pitm", 0, &pitm));
+    meta.extend_from_slice(&full_box(b"iloc", 0, &iloc));
+    meta.extend_from_slice(&full_box(b"iinf", 0, &iinf));
+    meta.extend_from_slice(&boxed(b"iprp", &iprp));
+    full_box(b"meta", 0, &meta)
+}
+
+fn boxed(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(payload.len() + 8);
+    out.extend_from_slice(&(payload.len() as u32 + 8).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(payload);
+    out
+}
+
+fn full_box(kind: &[u8; 4], version: u8, payload: &[u8]) -> Vec<u8> {
+    let mut out = vec![version, 0, 0, 0];
+    out.extend_from_slice(payload);
+    boxed(kind, &out)
+}
+
+fn nal_type(nal: &[u8]) -> u8 {
+    nal.first().map_or(0xff, |h| (h >> 1) & 0x3f)
+}
+
+/// Split the bitstream into NAL units (without start codes / length prefixes)
+fn split_nals(bitstream: &[u8]) -> Vec<&[u8]> {
+    if bitstream.starts_with(&[0, 0, 1]) || bitstream.starts_with(&[0, 0, 0, 1]) {
+        let mut nals = Vec::new();
+        let mut start = None;
+        let mut i = 0;
+        while i + 3 <= bitstream.len() {
+            if bitstream[i..i + 3] == [0, 0, 1] {
+                if let Some(start) = start {
+                    nals.push(trim_zeros(&bitstream[start..i]));
+                }
+                i += 3;
+                start = Some(i);
+            } else {
+                i += 1;
+            }
+        }
+        if let Some(start) = start {
+            nals.push(&bitstream[start..]);
+        }
+        nals.retain(|n| !n.is_empty());
+        nals
+    } else {
+        let mut nals = Vec::new();
+        let mut rest = bitstream;
+        while rest.len() >= 4 {
+            let len = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+            let Some(nal) = rest.get(4..4 + len) else {
+                break;
+            };
+            nals.push(nal);
+            rest = &rest[4 + len..];
+        }
+        nals
+    }
+}
+
+/// Strip the leading zero of a 4 byte start code which belongs to the previous NAL
+fn trim_zeros(nal: &[u8]) -> &[u8] {
+    let end = nal.iter().rposition(|&b| b != 0).map_or(0, |p| p + 1);
+    &nal[..end]
+}
+
+/// The parts of the sequence parameter set hvcC needs
+struct Sps {
+    /// general_profile_space .. general_level_idc
+    profile_tier_level: [u8; 12],
+    max_sub_layers: u8,
+    temporal_id_nesting: bool,
+    chroma_format_idc: u8,
+    bit_depth_luma_minus8: u8,
+    bit_depth_chroma_minus8: u8,
+}
+
+impl Sps {
+    fn parse(nal: &[u8]) -> Option<Self> {
+        // Drop the 2 byte NAL header and the emulation prevention bytes
+        let mut rbsp = Vec::with_capacity(nal.len());
+        let mut zeros = 0;
+        for &b in nal.get(2..)? {
+            if zeros >= 2 && b == 3 {
+                zeros = 0;
+                continue;
+            }
+            zeros = if b == 0 { zeros + 1 } else { 0 };
+            rbsp.push(b);
+        }
+
+        let mut bits = BitReader::new(&rbsp);
+        bits.skip(4)?;
+        let max_sub_layers_minus1 = bits.read(3)? as u8;
+        let temporal_id_nesting = bits.read(1)? == 1;
+        let profile_tier_level = rbsp.get(1..13)?.try_into().ok()?;
+        bits.skip(96)?;
+
+        let mut sub_layer_flags = Vec::new();
+        for _ in 0..max_sub_layers_minus1 {
+            sub_layer_flags.push((bits.read(1)? == 1, bits.read(1)? == 1));
+        }
+        if max_sub_layers_minus1 > 0 {
+            bits.skip(2 * (8 - max_sub_layers_minus1 as usize))?;
+        }
+        for (profile, level) in sub_layer_flags {
+            if profile {
+                bits.skip(88)?;
+            }
+            if level {
+                bits.skip(8)?;
+            }
+        }
+
+        // sps_seq_parameter_set_id
+        bits.read_ue()?;
+        let chroma_format_idc = bits.read_ue()?;
+        if chroma_format_idc == 3 {
+            // separate_colour_plane_flag
+            bits.skip(1)?;
+        }
+        // pic_width_in_luma_samples, pic_height_in_luma_samples
+        bits.read_ue()?;
+        bits.read_ue()?;
+        if bits.read(1)? == 1 {
+            // conformance window offsets
+            for _ in 0..4 {
+                bits.read_ue()?;
+            }
+        }
+        let bit_depth_luma_minus8 = bits.read_ue()?;
+        let bit_depth_chroma_minus8 = bits.read_ue()?;
+
+        Some(Self {
+            profile_tier_level,
+            max_sub_layers: max_sub_layers_minus1 + 1,
+            temporal_id_nesting,
+            chroma_format_idc: u8::try_from(chroma_format_idc).ok().filter(|c| *c <= 3)?,
+            bit_depth_luma_minus8: u8::try_from(bit_depth_luma_minus8)
+                .ok()
+                .filter(|b| *b <= 7)?,
+            bit_depth_chroma_minus8: u8::try_from(bit_depth_chroma_minus8)
+                .ok()
+                .filter(|b| *b <= 7)?,
+        })
+    }
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn read(&mut self, count: usize) -> Option<u32> {
+        let mut value = 0;
+        for _ in 0..count {
+            let byte = self.data.get(self.pos / 8)?;
+            value = (value << 1) | ((byte >> (7 - self.pos % 8)) & 1) as u32;
+            self.pos += 1;
+        }
+        Some(value)
+    }
+
+    fn skip(&mut self, count: usize) -> Option<()> {
+        if self.pos + count > self.data.len() * 8 {
+            return None;
+        }
+        self.pos += count;
+        Some(())
+    }
+
+    /// Unsigned exp-golomb
+    fn read_ue(&mut self) -> Option<u32> {
+        let mut zeros = 0;
+        while self.read(1)? == 0 {
+            zeros += 1;
+            if zeros > 31 {
+                return None;
+            }
+        }
+        Some((1 << zeros) - 1 + self.read(zeros)?)
+    }
+}
@@ -0,0 +1,344 @@
+//! ICC profiles of libraw's output color spaces
+//!
+//! The encoded images are tagged with the profile matching `output_color` and the output gamma
+//! (an APP2 segment in jpegs, iCCP in pngs, tag 34675 in tiffs) so viewers don't take every
+//! output for sRGB. The profiles are generated: a v2 matrix / TRC display profile with the
+//! primaries of the space adapted to D50 and libraw's gamma curve.
+
+use crate::options::{Gamma, OutputColorSpace};
+use crate::{LibrawError, Processor};
+
+/// The profile embedded into the encoded images
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum IccProfile {
+    /// The profile of `output_color` and gamma (nothing for [`OutputColorSpace::Raw`]), or the
+    /// file of `output_profile` if libraw is built with LCMS (see [`crate::options::LCMS`])
+    #[default]
+    Auto,
+    /// Don't embed a profile
+    None,
+    /// Embed this profile
+    Custom(Vec<u8>),
+}
+
+/// D50 illuminant of the profile connection space
+const D50: [f64; 3] = [0.9642, 1.0, 0.8249];
+const D65: (f64, f64) = (0.3127, 0.3290);
+const D50_XY: (f64, f64) = (0.3457, 0.3585);
+
+/// Entries of the curves, enough for 16 bit output with interpolation
+const CURVE_LEN: usize = 1024;
+
+type Matrix = [[f64; 3]; 3];
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut out = [[0.0; 3]; 3];
+    for (i, row) in out.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    out
+}
+
+fn apply(m: &Matrix, v: [f64; 3]) -> [f64; 3] {
+    [0, 1, 2].map(|i| (0..3).map(|k| m[i][k] * v[k]).sum())
+}
+
+fn invert(m: &Matrix) -> Matrix {
+    let det = m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+        - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+        + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0]);
+    let mut out = [[0.0; 3]; 3];
+    for (i, row) in out.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            // Cofactor of the transposed position
+            let (r0, r1) = ((j + 1) % 3, (j + 2) % 3);
+            let (c0, c1) = ((i + 1) % 3, (i + 2) % 3);
+            *value = (m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]) / det;
+        }
+    }
+    out
+}
+
+fn xy_to_xyz((x, y): (f64, f64)) -> [f64; 3] {
+    [x / y, 1.0, (1.0 - x - y) / y]
+}
+
+/// Bradford adaptation from the `white` point to D50
+fn adapt_to_d50(white: [f64; 3]) -> Matrix {
+    const BRADFORD: Matrix = [
+        [0.8951, 0.2664, -0.1614],
+        [-0.7502, 1.7135, 0.0367],
+        [0.0389, -0.0685, 1.0296],
+    ];
+    let source = apply(&BRADFORD, white);
+    let target = apply(&BRADFORD, D50);
+    let mut scale = [[0.0; 3]; 3];
+    for i in 0..3 {
+        scale[i][i] = target[i] / source[i];
+    }
+    multiply(&invert(&BRADFORD), &multiply(&scale, &BRADFORD))
+}
+
+/// RGB to XYZ of the primaries and white point
+fn rgb_to_xyz(primaries: [(f64, f64); 3], white: (f64, f64)) -> Matrix {
+    let columns = primaries.map(xy_to_xyz);
+    let m = [0, 1, 2].map(|i| [columns[0][i], columns[1][i], columns[2][i]]);
+    let scale = apply(&invert(&m), xy_to_xyz(white));
+    [0, 1, 2].map(|i| [0, 1, 2].map(|j| m[i][j] * scale[j]))
+}
+
+/// Name and RGB to XYZ (D50) matrix of a color space
+fn space(space: OutputColorSpace) -> Option<(&'static str, Matrix)> {
+    let (name, primaries, white) = match space {
+        OutputColorSpace::Raw => return None,
+        OutputColorSpace::Srgb => ("sRGB", [(0.64, 0.33), (0.30, 0.60), (0.15, 0.06)], D65),
+        OutputColorSpace::AdobeRgb => (
+            "Adobe RGB compatible",
+            [(0.64, 0.33), (0.21, 0.71), (0.15, 0.06)],
+            D65,
+        ),
+        OutputColorSpace::WideGamut => (
+            "Wide Gamut RGB",
+            [(0.7347, 0.2653), (0.1152, 0.8264), (0.1566, 0.0177)],
+            D50_XY,
+        ),
+        OutputColorSpace::ProPhoto => (
+            "ProPhoto RGB",
+            [(0.7347, 0.2653), (0.1596, 0.8404), (0.0366, 0.0001)],
+            D50_XY,
+        ),
+        OutputColorSpace::Aces => (
+            "ACES",
+            [(0.7347, 0.2653), (0.0, 1.0), (0.0001, -0.0770)],
+            (0.32168, 0.33767),
+        ),
+        // libraw writes XYZ relative to D65
+        OutputColorSpace::Xyz => {
+            let identity = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+            return Some(("XYZ", multiply(&adapt_to_d50(xy_to_xyz(D65)), &identity)));
+        }
+    };
+    let matrix = multiply(
+        &adapt_to_d50(xy_to_xyz(white)),
+        &rgb_to_xyz(primaries, white),
+    );
+    Some((name, matrix))
+}
+
+/// The curve parameters of libraw's gamma_curve for `power` (gamm[0]) and toe `slope`
+fn gamma_params(power: f64, slope: f64) -> [f64; 5] {
+    let mut g = [power, slope, 0.0, 0.0, 0.0];
+    let mut bounds = [0.0, 0.0];
+    bounds[(g[1] >= 1.0) as usize] = 1.0;
+    if g[1] != 0.0 && (g[1] - 1.0) * (g[0] - 1.0) <= 0.0 {
+        for _ in 0..48 {
+            g[2] = (bounds[0] + bounds[1]) / 2.0;
+            let upper = if g[0] != 0.0 {
+                ((g[2] / g[1]).powf(-g[0]) - 1.0) / g[0] - 1.0 / g[2] > -1.0
+            } else {
+                g[2] / (1.0 - 1.0 / g[2]).exp() < g[1]
+            };
+            bounds[upper as usize] = g[2];
+        }
+        g[3] = g[2] / g[1];
+        if g[0] != 0.0 {
+            g[4] = g[2] * (1.0 / g[0] - 1.0);
+        }
+    }
+    g
+}
+
+/// The encoded to linear curve of `gamma`, empty for linear
+fn curve(gamma: Gamma) -> Vec<u16> {
+    if gamma == Gamma::Linear {
+        return Vec::new();
+    }
+    let (power, slope) = gamma.curve();
+    let g = gamma_params(1.0 / power, slope);
+    (0..CURVE_LEN)
+        .map(|i| {
+            let v = i as f64 / (CURVE_LEN - 1) as f64;
+            let linear = if v < g[2] {
+                v / g[1]
+            } else if g[0] != 0.0 {
+                ((v + g[4]) / (1.0 + g[4])).powf(1.0 / g[0])
+            } else {
+                ((v - 1.0) / g[2]).exp()
+            };
+            (linear.clamp(0.0, 1.0) * 65535.0).round() as u16
+        })
+        .collect()
+}
+
+fn s15_fixed16(value: f64) -> [u8; 4] {
+    ((value * 65536.0).round() as i32).to_be_bytes()
+}
+
+fn xyz_tag(xyz: [f64; 3]) -> Vec<u8> {
+    let mut tag = b"XYZ \0\0\0\0".to_vec();
+    for value in xyz {
+        tag.extend_from_slice(&s15_fixed16(value));
+    }
+    tag
+}
+
+/// v2 textDescriptionType with only the ascii description
+fn desc_tag(text: &str) -> Vec<u8> {
+    let mut tag = b"desc\0\0\0\0".to_vec();
+    tag.extend_from_slice(&(text.len() as u32 + 1).to_be_bytes());
+    tag.extend_from_slice(text.as_bytes());
+    tag.push(0);
+    // Empty unicode (language, count) and scriptcode (code, count, 67 bytes) descriptions
+    tag.extend_from_slice(&[0; 8]);
+    tag.extend_from_slice(&[0; 3 + 67]);
+    tag
+}
+
+fn text_tag(text: &str) -> Vec<u8> {
+    let mut tag = b"text\0\0\0\0".to_vec();
+    tag.extend_from_slice(text.as_bytes());
+    tag.push(0);
+    tag
+}
+
+fn curve_tag(curve: &[u16]) -> Vec<u8> {
+    let mut tag = b"curv\0\0\0\0".to_vec();
+    tag.extend_from_slice(&(curve.len() as u32).to_be_bytes());
+    tag.extend(curve.iter().flat_map(|v| v.to_be_bytes()));
+    tag
+}
+
+/// ICC profile of the images libraw produces with `output_color` = `space` and `gamma`
+///
+/// [`None`] for [`OutputColorSpace::Raw`] which has no defined colors.
+pub fn output_profile(space: OutputColorSpace, gamma: Gamma) -> Option<Vec<u8>> {
+    let (name, matrix) = self::space(space)?;
+    let (power, slope) = gamma.curve();
+    let description = format!("{} (libraw gamma {:.3}, slope {:.2})", name, power, slope);
+    let column = |i: usize| [matrix[0][i], matrix[1][i], matrix[2][i]];
+    let trc = curve_tag(&curve(gamma));
+    let tags: [(&[u8; 4], Vec<u8>); 9] = [
+        (b"desc", desc_tag(&description)),
+        (b"cprt", text_tag("No copyright, use freely")),
+        (b"wtpt", xyz_tag(D50)),
+        (b"rXYZ", xyz_tag(column(0))),
+        (b"gXYZ", xyz_tag(column(1))),
+        (b"bXYZ", xyz_tag(column(2))),
+        (b"rTRC", trc.clone()),
+        (b"gTRC", trc.clone()),
+        (b"bTRC", trc),
+    ];
+
+    let mut table = (tags.len() as u32).to_be_bytes().to_vec();
+    let mut data = Vec::new();
+    let data_start = 128 + 4 + 12 * tags.len();
+    let mut shared_trc = None;
+    for (signature, tag) in &tags {
+        // The three curves share the same data
+        let offset = match (signature.ends_with(b"TRC"), shared_trc) {
+            (true, Some(offset)) => offset,
+            _ => {
+                let offset = data_start + data.len();
+                data.extend_from_slice(tag);
+                // Tags start on 4 byte boundaries
+                data.resize((data.len() + 3) / 4 * 4, 0);
+                offset
+            }
+        };
+        if signature.ends_with(b"TRC") {
+            shared_trc = Some(offset);
+        }
+        table.extend_from_slice(*signature);
+        table.extend_from_slice(&(offset as u32).to_be_bytes());
+        table.extend_from_slice(&(tag.len() as u32).to_be_bytes());
+    }
+
+    let size = data_start + data.len();
+    let mut profile = Vec::with_capacity(size);
+    profile.extend_from_slice(&(size as u32).to_be_bytes());
+    // Preferred CMM
+    profile.extend_from_slice(&[0; 4]);
+    // Version 2.1
+    profile.extend_from_slice(&[2, 0x10, 0, 0]);
+    profile.extend_from_slice(b"mntrRGB XYZ ");
+    // Creation date 2000-01-01 so the profiles are reproducible
+    for value in [2000u16, 1, 1, 0, 0, 0] {
+        profile.extend_from_slice(&value.to_be_bytes());
+    }
+    profile.extend_from_slice(b"acsp");
+    // Platform, flags, manufacturer, model, attributes, rendering intent (perceptual)
+    profile.extend_from_slice(&[0; 4 + 4 + 4 + 4 + 8 + 4]);
+    for value in D50 {
+        profile.extend_from_slice(&s15_fixed16(value));
+    }
+    // Creator, profile id and reserved
+    profile.resize(128, 0);
+    profile.extend_from_slice(&table);
+    profile.extend_from_slice(&data);
+    Some(profile)
+}
+
+impl Processor {
+    /// The profile embedded into the encoded images, [`IccProfile::Auto`] by default
+    pub fn icc_profile(&self) -> &IccProfile {
+        &self.icc_profile
+    }
+
+    /// Set the profile embedded into every image encoded from the processed raw
+    ///
+    /// Embedded previews keep whatever they contain.
+    pub fn set_icc_profile(&mut self, profile: IccProfile) {
+        self.icc_profile = profile;
+    }
+
+    /// The bytes `profile` resolves to with the current options
+    ///
+    /// [`IccProfile::Auto`] is the profile of `output_color` and gamma, libraw only converts to
+    /// the file of `output_profile` when it's built with LCMS.
+    pub fn resolve_icc_profile(
+        &self,
+        profile: &IccProfile,
+    ) -> Result<Option<Vec<u8>>, LibrawError> {
+        match profile {
+            IccProfile::Auto => {
+                let options = self.processing_options();
+                match options.output_profile {
+                    Some(path) if crate::options::LCMS => Ok(Some(std::fs::read(path)?)),
+                    _ => Ok(output_profile(options.output_color, options.gamma)),
+                }
+            }
+            IccProfile::None => Ok(None),
+            IccProfile::Custom(profile) => Ok(Some(profile.clone())),
+        }
+    }
+
+    /// The bytes of [`Processor::icc_profile`], see [`Processor::resolve_icc_profile`]
+    pub fn output_icc_profile(&self) -> Result<Option<Vec<u8>>, LibrawError> {
+        self.resolve_icc_profile(&self.icc_profile)
+    }
+
+    /// `profile` for an image with `colors`, the rgb profiles don't fit anything else
+    pub(crate) fn icc_profile_for(
+        &self,
+        profile: &IccProfile,
+        colors: u16,
+    ) -> Result<Option<Vec<u8>>, LibrawError> {
+        match profile {
+            IccProfile::Auto if colors != 3 => Ok(None),
+            profile => self.resolve_icc_profile(profile),
+        }
+    }
+}
+
+/// Put `profile` into the APP2 segments of a jpeg
+#[cfg(feature = "jpeg")]
+pub(crate) fn set_jpeg_icc(jpeg: Vec<u8>, profile: &[u8]) -> Result<Vec<u8>, crate::LibrawError> {
+    use img_parts::ImageICC;
+    let mut parts = img_parts::jpeg::Jpeg::from_bytes(jpeg.into())?;
+    parts.set_icc_profile(Some(profile.to_vec().into()));
+    let mut jpeg = Vec::new();
+    parts.encoder().write_to(&mut jpeg)?;
+    Ok(jpeg)
+}
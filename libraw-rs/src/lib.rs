@@ -1,15 +1,56 @@
 #[macro_use]
 pub mod error;
+pub mod bayer;
+mod datastream;
 pub mod dcraw;
 pub mod defaults;
+#[cfg(any(
+    feature = "jpeg",
+    feature = "png",
+    feature = "tiff",
+    feature = "webp",
+    feature = "avif"
+))]
+pub mod encode;
 #[cfg(feature = "exif")]
 pub mod exif;
+pub mod exif_builder;
+pub mod frames;
+pub mod heif;
+pub mod icc;
+pub mod mem_image;
+pub mod metadata;
+pub mod options;
 pub mod orientation;
+mod ppm_tiff;
+pub mod presets;
+mod processed;
 pub mod progress;
+pub mod raw;
+pub mod raw_options;
+#[cfg(feature = "image")]
+pub mod resize;
+pub mod thumbnail;
+mod tiff;
 pub mod traits;
+pub mod typestate;
 
 use alloc::sync::Arc;
+#[cfg(any(
+    feature = "jpeg",
+    feature = "png",
+    feature = "tiff",
+    feature = "webp",
+    feature = "avif"
+))]
+pub use encode::{EncodeOptions, OutputFormat};
 pub use error::LibrawError;
+pub use mem_image::{MemImageFormat, PixelLayout};
+pub use options::ProcessingOptions;
+pub use orientation::{Flip, Orientation};
+pub use raw_options::RawOptions;
+#[cfg(feature = "image")]
+pub use resize::Resize;
 
 extern crate alloc;
 extern crate libraw_sys as sys;
@@ -35,8 +76,23 @@ pub const fn version() -> Version {
 pub struct Processor {
     inner: NonNull<sys::libraw_data_t>,
     dropped: Arc<AtomicBool>,
+    /// Whatever libraw is reading the current file from, kept alive until the next recycle
+    input: Option<Box<dyn core::any::Any + Send>>,
+    /// Write the metadata as EXIF into encoded jpegs
+    embed_exif: bool,
+    /// Embedded into the images encoded from the processed raw
+    icc_profile: icc::IccProfile,
+    /// The path strings of [`ProcessingOptions`] libraw points to
+    option_strings: options::OptionStrings,
+    /// Written again after every recycle
+    raw_options: Option<raw_options::AppliedRawOptions>,
+    /// Opens the current file again, see [`Processor::reopen`]
+    reopen: Option<Reopen>,
 }
 
+/// Calls the libraw open function of the current file again, `input` has to be alive
+type Reopen = Box<dyn FnMut(&mut Processor) -> Result<(), LibrawError> + Send>;
+
 /// You can pass the Processor to another thread since it doesn't use any thread_local values
 unsafe impl Send for Processor {}
 /// You can pass the reference to Processor to another thread since it cannot open / close / drop
@@ -114,6 +170,12 @@ impl Processor {
         Self {
             inner: NonNull::new(inner).expect("Failed to initialize libraw"),
             dropped: Arc::new(AtomicBool::new(false)),
+            input: None,
+            embed_exif: true,
+            icc_profile: Default::default(),
+            option_strings: Default::default(),
+            raw_options: None,
+            reopen: None,
         }
     }
 
@@ -127,6 +189,12 @@ impl Processor {
             Ok(Self {
                 inner: NonNull::new(inner).expect("Failed to initialize libraw"),
                 dropped: Arc::new(AtomicBool::new(false)),
+                input: None,
+                embed_exif: true,
+                icc_profile: Default::default(),
+                option_strings: Default::default(),
+                raw_options: None,
+                reopen: None,
             })
         }
     }
@@ -146,7 +214,7 @@ impl Processor {
             let c_path = path_to_cstr(&path)?;
             LibrawError::check(unsafe {
                 sys::libraw_open_file(self.inner.as_ptr(), c_path.as_ptr())
-            })
+            })?;
         }
 
         #[cfg(windows)]
@@ -154,8 +222,12 @@ impl Processor {
             let c_path = path_to_widestring(&path)?;
             LibrawError::check(unsafe {
                 sys::libraw_open_wfile(self.inner.as_ptr(), c_path.as_ptr())
-            })
+            })?;
         }
+
+        let path = path.as_ref().to_path_buf();
+        self.reopen = Some(Box::new(move |p| p.open(&path)));
+        Ok(())
     }
 
     #[cfg(windows)]
@@ -169,16 +241,47 @@ impl Processor {
         LibrawError::check(unsafe { sys::libraw_open_file(self.inner.as_ptr(), c_path.as_ptr()) })
     }
 
-    pub fn open_buffer(&mut self, buffer: impl AsRef<[u8]>) -> Result<(), LibrawError> {
+    /// Calls libraw_open_buffer
+    ///
+    /// libraw keeps reading from the buffer in `unpack` / `unpack_thumb` so the processor takes
+    /// ownership of it and keeps it alive until the next open / recycle or until it is dropped.
+    ///
+    /// Anything which can be viewed as bytes works, eg. `Vec<u8>`, `&'static [u8]`, `Arc<[u8]>`
+    /// or `bytes::Bytes`
+    pub fn open_buffer<B>(&mut self, buffer: B) -> Result<(), LibrawError>
+    where
+        B: AsRef<[u8]> + Send + 'static,
+    {
         self.recycle()?;
-        let buffer = buffer.as_ref();
-        LibrawError::check(unsafe {
-            sys::libraw_open_buffer(
-                self.inner.as_ptr(),
-                buffer.as_ptr() as *const libc::c_void,
-                buffer.len(),
-            )
-        })
+        // Box the buffer before taking the pointer so that inline buffers (arrays) don't move
+        let buffer = Box::new(buffer);
+        let data = (*buffer).as_ref();
+        let (ptr, len) = (data.as_ptr() as usize, data.len());
+        self.input = Some(buffer);
+        let open = move |p: &mut Processor| {
+            LibrawError::check(unsafe {
+                sys::libraw_open_buffer(p.inner.as_ptr(), ptr as *const libc::c_void, len)
+            })
+        };
+        open(self)?;
+        self.reopen = Some(Box::new(open));
+        Ok(())
+    }
+
+    /// Memory map the file at `path` and open it with libraw_open_buffer
+    ///
+    /// Avoids both the many small reads of [`Processor::open`] and the copy of the whole file into
+    /// memory needed for [`Processor::open_buffer`]. The mapping is kept alive until the next
+    /// open / recycle or until the processor is dropped.
+    ///
+    /// # Safety
+    /// The file must not be modified or truncated (by this or any other process) while it is
+    /// mapped, see [`memmap2::Mmap`]
+    #[cfg(feature = "mmap")]
+    pub unsafe fn open_mmap(&mut self, path: impl AsRef<Path>) -> Result<(), LibrawError> {
+        let file = std::fs::File::open(path)?;
+        let mmap = memmap2::Mmap::map(&file)?;
+        self.open_buffer(mmap)
     }
 
     /// Get the shootinginfo struct from libraw_data_t
@@ -291,9 +394,30 @@ impl Processor {
     /// All other references should be invalid when we recycle so we take a mutable value to self
     pub fn recycle(&mut self) -> Result<(), LibrawError> {
         unsafe { sys::libraw_recycle(self.inner.as_ptr()) };
+        // libraw doesn't hold any references to the input after recycle
+        self.input = None;
+        self.reopen = None;
+        self.restore_raw_options();
         Ok(())
     }
 
+    /// Recycle and open the current file again from the same path / buffer / reader
+    ///
+    /// Picks up the raw options set since the file was opened, eg. [`Processor::select_frame`].
+    /// Returns [`error::InternalLibrawError::OutOfOrderCall`] if no file has been opened.
+    pub fn reopen(&mut self) -> Result<(), LibrawError> {
+        let mut reopen = self.reopen.take().ok_or(LibrawError::InternalError(
+            error::InternalLibrawError::OutOfOrderCall,
+        ))?;
+        // Keep the input alive through the recycle
+        let input = self.input.take();
+        self.recycle()?;
+        self.input = input;
+        let result = reopen(self);
+        self.reopen = Some(reopen);
+        result
+    }
+
     /// Adjusts sizes and changes the resolution according to the flip values
     ///
     /// Also considers 45 degree angles for fuji cameras
@@ -311,82 +435,75 @@ impl Processor {
     /// So no need for doing flips
     /// Consider ~20ms
     pub fn get_jpeg(&mut self) -> Result<Vec<u8>, LibrawError> {
-        // First check if unpack_thumb has already been called.
-        // If yes then don't call it
-
-        // Check if (*inner).thumbnail.thumb is null
-        if unsafe { (*self.inner.as_ptr()).thumbnail.thumb.is_null() } {
-            self.unpack_thumb()?;
-        }
         let flip = self.sizes().flip;
-        let thumbnail = self.thumbnail();
-        let thumbnail_data = unsafe {
-            std::slice::from_raw_parts(thumbnail.thumb as *const u8, thumbnail.tlength as usize)
-        };
+        self.thumbnail_jpeg(Some(Orientation::from(Flip::from(flip))))
+    }
 
-        match ThumbnailFormat::from(thumbnail.tformat) {
-            ThumbnailFormat::Jpeg => {
-                // Since the buffer is already a jpeg buffer return it as-is
-                //
-                // Don't use a Vec since a Vec's internal memory representation is entirely dependent
-                // on the allocator used which might(is) be different in c/c++/rust
-                let jpeg = thumbnail_data.to_vec();
-                let jpeg = Orientation::from(Flip::from(flip)).add_to(jpeg)?;
-                Ok(jpeg)
-            }
-            ThumbnailFormat::Bitmap => {
+    /// Get the jpeg without rotation
+    pub fn get_jpeg_no_rotation(&mut self) -> Result<Vec<u8>, LibrawError> {
+        self.thumbnail_jpeg(None)
+    }
+
+    /// Encode the unpacked thumbnail as a jpeg and tag it with `orientation`
+    pub(crate) fn thumbnail_jpeg(
+        &mut self,
+        orientation: Option<Orientation>,
+    ) -> Result<Vec<u8>, LibrawError> {
+        let thumbnail = self.decode_thumbnail()?.into_rgb8();
+        let jpeg = match thumbnail.data {
+            // Since the buffer is already a jpeg buffer return it as-is
+            thumbnail::ThumbnailData::Jpeg(jpeg) => jpeg,
+            thumbnail::ThumbnailData::Rgb8(rgb) => {
                 // Since this is a bitmap we have to generate the thumbnail from the rgb data from
                 // here
                 let mut jpeg = Vec::new();
                 image::codecs::jpeg::JpegEncoder::new(&mut jpeg).encode(
-                    thumbnail_data,
-                    thumbnail.twidth as u32,
-                    thumbnail.theight as u32,
+                    &rgb,
+                    thumbnail.width,
+                    thumbnail.height,
                     image::ColorType::Rgb8,
                 )?;
-                let jpeg = Orientation::from(Flip::from(flip)).add_to(jpeg)?;
-                Ok(jpeg)
+                jpeg
             }
-            _ => Err(LibrawError::UnsupportedThumbnail),
+            _ => return Err(LibrawError::UnsupportedThumbnail),
+        };
+        match orientation {
+            Some(orientation) => self.tag_jpeg(jpeg, Some(orientation)),
+            // Leave the exif of the preview alone
+            None => Ok(jpeg),
         }
     }
 
-    /// Get the jpeg without rotation
-    pub fn get_jpeg_no_rotation(&mut self) -> Result<Vec<u8>, LibrawError> {
-        // First check if unpack_thumb has already been called.
-        // If yes then don't call it
-
-        // Check if (*inner).thumbnail.thumb is null
-        if unsafe { self.inner.as_ref().thumbnail.thumb.is_null() } {
-            self.unpack_thumb()?;
-        }
-        let thumbnail = self.thumbnail();
-        let thumbnail_data = unsafe {
-            std::slice::from_raw_parts(thumbnail.thumb as *const u8, thumbnail.tlength as usize)
+    /// Embed the EXIF from [`Processor::exif_builder`] (if enabled and the jpeg doesn't have an
+    /// EXIF yet) and set the orientation
+    fn tag_jpeg(
+        &self,
+        jpeg: Vec<u8>,
+        orientation: Option<Orientation>,
+    ) -> Result<Vec<u8>, LibrawError> {
+        let jpeg = if self.embed_exif {
+            let exif = self.exif_builder().without_orientation().build();
+            orientation::set_jpeg_exif(jpeg, &exif, true)?
+        } else {
+            jpeg
         };
+        match orientation {
+            Some(orientation) => orientation.add_to(jpeg),
+            None => Ok(jpeg),
+        }
+    }
 
-        match ThumbnailFormat::from(thumbnail.tformat) {
-            ThumbnailFormat::Jpeg => {
-                // Since the buffer is already a jpeg buffer return it as-is
-                //
-                // Don't use a Vec since a Vec's internal memory representation is entirely dependent
-                // on the allocator used which might(is) be different in c/c++/rust
-                let jpeg = thumbnail_data.to_vec();
-                Ok(jpeg)
-            }
-            ThumbnailFormat::Bitmap => {
-                // Since this is a bitmap we have to generate the thumbnail from the rgb data from
-                // here
-                let mut jpeg = Vec::new();
-                image::codecs::jpeg::JpegEncoder::new(&mut jpeg).encode(
-                    thumbnail_data,
-                    thumbnail.twidth as u32,
-                    thumbnail.theight as u32,
-                    image::ColorType::Rgb8,
-                )?;
-                Ok(jpeg)
-            }
-            _ => Err(LibrawError::UnsupportedThumbnail),
+    /// [`Processor::tag_jpeg`] a jpeg of the processed image and embed
+    /// [`Processor::output_icc_profile`]
+    fn tag_processed_jpeg(
+        &self,
+        jpeg: Vec<u8>,
+        orientation: Option<Orientation>,
+    ) -> Result<Vec<u8>, LibrawError> {
+        let jpeg = self.tag_jpeg(jpeg, orientation)?;
+        match self.output_icc_profile()? {
+            Some(profile) => icc::set_jpeg_icc(jpeg, &profile),
+            None => Ok(jpeg),
         }
     }
 
@@ -425,18 +542,20 @@ impl Processor {
                 };
                 let mut jpeg = Vec::new();
                 image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg, quality).encode(
-                    _processed.as_slice(),
+                    _processed.as_slice_u8(),
                     processed.width as u32,
                     processed.height as u32,
                     colortype,
                 )?;
-                let jpeg = Orientation::from(Flip::from(flip)).add_to(jpeg)?;
+                let jpeg =
+                    self.tag_processed_jpeg(jpeg, Some(Orientation::from(Flip::from(flip))))?;
                 Ok(jpeg)
             }
             ImageFormat::Jpeg => {
                 // structure contain in-memory image of JPEG file. Only type, data_size and data fields are valid (and nonzero);
-                let jpeg = _processed.as_slice().to_vec();
-                let jpeg = Orientation::from(Flip::from(flip)).add_to(jpeg)?;
+                let jpeg = _processed.as_slice_u8().to_vec();
+                let jpeg =
+                    self.tag_processed_jpeg(jpeg, Some(Orientation::from(Flip::from(flip))))?;
                 Ok(jpeg)
             }
         }
@@ -471,17 +590,17 @@ impl Processor {
                 };
                 let mut jpeg = Vec::new();
                 image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg, quality).encode(
-                    _processed.as_slice(),
+                    _processed.as_slice_u8(),
                     processed.width as u32,
                     processed.height as u32,
                     colortype,
                 )?;
-                Ok(jpeg)
+                self.tag_processed_jpeg(jpeg, None)
             }
             ImageFormat::Jpeg => {
                 // structure contain in-memory image of JPEG file. Only type, data_size and data fields are valid (and nonzero);
-                let jpeg = _processed.as_slice().to_vec();
-                Ok(jpeg)
+                let jpeg = _processed.as_slice_u8().to_vec();
+                self.tag_processed_jpeg(jpeg, None)
             }
         }
     }
@@ -489,75 +608,53 @@ impl Processor {
     /// Same as to_jpeg but with resize to resolution
     /// This will be even slower than to_jpeg since it also has to resize
     /// Consider ~200ms
+    ///
+    /// The image is fit into `resolution` with [`resize::ResizeFilter::Lanczos3`] and processed
+    /// with half_size when that is at most half the size of the sensor, see
+    /// [`Processor::to_jpeg_resized`] for the other options. `resize_jpeg` only matters if libraw
+    /// produces a jpeg, bitmaps are always resized.
     pub fn to_jpeg_with_resolution(
         &mut self,
         resolution: impl IntoResolution,
         resize_jpeg: bool,
         quality: u8,
     ) -> Result<Vec<u8>, LibrawError> {
-        // Now check if libraw_unpack has been called already
-        // If it has been call inner.image shouldn't be null
-        if unsafe { self.inner.as_ref().image.is_null() } {
-            self.unpack()?;
-        }
-        self.dcraw_process()?;
-        let flip = self.sizes().flip;
-        let _processed = self.dcraw_process_make_mem_image()?;
-        let processed = _processed.raw();
-
-        // let data = unsafe {
-        //     std::slice::from_raw_parts(
-        //         processed.data.as_ptr() as *const u8,
-        //         processed.data_size as usize,
-        //     )
-        // };
         let res = resolution.into_resolution();
-        match ImageFormat::from(processed.type_) {
-            ImageFormat::Bitmap => {
-                let mut jpeg = std::io::Cursor::new(Vec::new());
-                let dynimg = match processed.bits {
-                    8 => image::DynamicImage::from(
-                        image::ImageBuffer::<image::Rgb<u8>, Vec<u8>>::from_raw(
-                            processed.width.into(),
-                            processed.height.into(),
-                            _processed.as_slice().to_vec(),
-                        )
-                        .ok_or(LibrawError::EncodingError)?,
-                    ),
-                    16 => image::DynamicImage::from(
-                        image::ImageBuffer::<image::Rgb<u16>, Vec<u16>>::from_raw(
-                            processed.width.into(),
-                            processed.height.into(),
-                            _processed.as_slice().to_vec(),
-                        )
-                        .ok_or(LibrawError::EncodingError)?,
-                    ),
-                    _ => return Err(LibrawError::InvalidColor(processed.bits)),
-                };
-                dynimg.write_to(&mut jpeg, image::ImageOutputFormat::Jpeg(quality))?;
-                let jpeg = jpeg.into_inner();
-                let jpeg = Orientation::from(Flip::from(flip)).add_to(jpeg)?;
-                Ok(jpeg)
-            }
-            ImageFormat::Jpeg => {
-                // structure contain in-memory image of JPEG file. Only type, data_size and data fields are valid (and nonzero);
-                let mut jpeg = _processed.as_slice().to_vec();
-                if resize_jpeg {
-                    use image::io::Reader;
-                    use std::io::Cursor;
-                    let dynimg = Reader::new(&mut Cursor::new(jpeg.drain(..)))
-                        .with_guessed_format()?
-                        .decode()?
-                        .thumbnail(res.width, res.height);
-                    dynimg.write_to(
-                        &mut Cursor::new(&mut jpeg),
-                        image::ImageOutputFormat::Jpeg(quality),
-                    )?;
-                }
-                let jpeg = Orientation::from(Flip::from(flip)).add_to(jpeg)?;
-                Ok(jpeg)
+        let resize = resize::Resize::new(res.width, res.height);
+        self.resized_jpeg(&resize, resize_jpeg, quality)
+    }
+
+    /// Process the raw, resize it with `resize` and encode it as a jpeg
+    pub fn to_jpeg_resized(
+        &mut self,
+        resize: &resize::Resize,
+        quality: u8,
+    ) -> Result<Vec<u8>, LibrawError> {
+        self.resized_jpeg(resize, true, quality)
+    }
+
+    fn resized_jpeg(
+        &mut self,
+        resize: &resize::Resize,
+        resize_jpeg: bool,
+        quality: u8,
+    ) -> Result<Vec<u8>, LibrawError> {
+        resize.validate()?;
+        let half_size = self.wants_half_size(resize);
+        let processed = self.process_image(half_size)?;
+        let flip = self.sizes().flip;
+        let jpeg = match processed.type_() {
+            // structure contain in-memory image of JPEG file. Only type, data_size and data fields are valid (and nonzero);
+            ImageFormat::Jpeg if !resize_jpeg => processed.into_vec_u8()?,
+            _ => {
+                let dynimg = resize.apply(image::DynamicImage::try_from(processed)?);
+                let mut jpeg = Vec::new();
+                image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg, quality)
+                    .encode_image(&dynimg.into_rgb8())?;
+                jpeg
             }
-        }
+        };
+        self.tag_processed_jpeg(jpeg, Some(Orientation::from(Flip::from(flip))))
     }
 
     /// This will first try get_jpeg and then fallback to to_jpeg
@@ -608,6 +705,8 @@ impl Processor {
 /// The builder struct for Processor
 pub struct ProcessorBuilder {
     inner: NonNull<sys::libraw_data_t>,
+    option_strings: options::OptionStrings,
+    raw_options: Option<raw_options::AppliedRawOptions>,
 }
 
 impl ProcessorBuilder {
@@ -615,13 +714,30 @@ impl ProcessorBuilder {
         Self::default()
     }
 
-    pub fn build(self) -> Processor {
-        Processor {
+    pub fn build(mut self) -> Processor {
+        let processor = Processor {
             inner: self.inner,
             dropped: Arc::new(AtomicBool::new(false)),
-        }
+            input: None,
+            embed_exif: true,
+            icc_profile: Default::default(),
+            option_strings: core::mem::take(&mut self.option_strings),
+            raw_options: self.raw_options.take(),
+            reopen: None,
+        };
+        // The processor owns libraw_data_t now
+        core::mem::forget(self);
+        processor
     }
 
+    /// Validate `options` and write them to the params of the processor
+    pub fn with_options(mut self, options: &ProcessingOptions) -> Result<Self, LibrawError> {
+        let params = unsafe { &mut self.inner.as_mut().params };
+        self.option_strings = options::write_options(params, options)?;
+        Ok(self)
+    }
+
+    #[allow(deprecated)]
     pub fn with_params<P: IntoIterator<Item = Params>>(mut self, params: P) -> Self {
         let libraw_params = unsafe { &mut self.inner.as_mut().params };
         use Params::*;
@@ -668,12 +784,21 @@ impl ProcessorBuilder {
         self
     }
 }
+/// Closes the processor if the builder is dropped without calling build (eg. on an error)
+impl Drop for ProcessorBuilder {
+    fn drop(&mut self) {
+        unsafe { sys::libraw_close(self.inner.as_ptr()) };
+    }
+}
+
 impl Default for ProcessorBuilder {
     fn default() -> Self {
         let inner = unsafe { sys::libraw_init(LibrawConstructorFlags::None as u32) };
         assert!(!inner.is_null());
         Self {
             inner: NonNull::new(inner).expect("non null"),
+            option_strings: Default::default(),
+            raw_options: None,
         }
     }
 }
@@ -683,6 +808,11 @@ pub struct ProcessedImage {
     inner: NonNull<sys::libraw_processed_image_t>,
 }
 
+/// The image is a single malloc'd block owned by this handle, libraw keeps no reference to it
+unsafe impl Send for ProcessedImage {}
+/// Only `apply_orientation` writes to the data and it needs a mutable reference
+unsafe impl Sync for ProcessedImage {}
+
 impl Drop for ProcessedImage {
     fn drop(&mut self) {
         unsafe { sys::libraw_dcraw_clear_mem(self.inner.as_ptr()) }
@@ -707,13 +837,39 @@ impl ProcessedImage {
         unsafe { self.inner.as_ref() }
     }
     pub fn as_slice_u8(&self) -> &[u8] {
-        self.as_slice::<u8>()
+        self.data::<u8>()
     }
     pub fn as_slice_u16(&self) -> &[u16] {
-        self.as_slice::<u16>()
+        self.data::<u16>()
     }
 
+    /// The data reinterpreted as `T`
+    ///
+    /// Panics if `T` isn't the size of a sample (a byte for jpegs) or the data isn't aligned
+    /// for it.
+    #[deprecated(note = "use the checked rgb8 / rgb16 / gray8 / gray16 views or as_slice_u8")]
     pub fn as_slice<T>(&self) -> &[T] {
+        let sample = match self.type_() {
+            ImageFormat::Bitmap => self.bits() as usize / 8,
+            ImageFormat::Jpeg => 1,
+        };
+        assert_eq!(
+            std::mem::size_of::<T>(),
+            sample,
+            "the size of T doesn't match the samples"
+        );
+        let data = unsafe { self.inner.as_ref().data.as_ptr() };
+        assert_eq!(
+            data as usize % std::mem::align_of::<T>(),
+            0,
+            "the data isn't aligned for T"
+        );
+        self.data::<T>()
+    }
+
+    /// Only for `u8` and `u16`, the data follows the 16 byte header of the malloc'd struct so
+    /// it's aligned for both
+    fn data<T>(&self) -> &[T] {
         unsafe {
             std::slice::from_raw_parts(
                 self.inner.as_ref().data.as_ptr() as *const T,
@@ -741,8 +897,10 @@ impl ProcessedImage {
     }
 }
 
+/// Raw libraw_output_params_t values
 #[derive(Debug)]
 #[non_exhaustive]
+#[deprecated(note = "use ProcessingOptions and ProcessorBuilder::with_options")]
 pub enum Params {
     Greybox([u32; 4]),
     Cropbox([u32; 4]),
@@ -799,6 +957,7 @@ pub enum LibrawConstructorFlags {
 }
 
 /// The thumbnail types that might be embedded inside a raw file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[non_exhaustive]
 #[cfg_attr(all(windows, target_env = "msvc"), repr(i32))]
 #[cfg_attr(all(windows, target_env = "gnu"), repr(u32))]
@@ -919,173 +1078,3 @@ impl IntoResolution for [u16; 2] {
         }
     }
 }
-
-/// exif::Tag::Orientation
-/// Possible values 1,2,3,4,5,6,7,8
-/// 2, 5, 7 and 4 are mirrored images and not implemented
-#[derive(Debug, Eq, PartialEq)]
-pub struct Orientation(pub u8);
-impl PartialEq<u8> for Orientation {
-    fn eq(&self, other: &u8) -> bool {
-        &self.0 == other
-    }
-}
-impl PartialEq<Orientation> for u8 {
-    fn eq(&self, other: &Orientation) -> bool {
-        self == &other.0
-    }
-}
-
-impl std::ops::Add for Orientation {
-    type Output = Self;
-    fn add(self, rhs: Orientation) -> Self::Output {
-        Self(match (self.0, rhs.0) {
-            (1, o) => o,
-            (o, 1) => o,
-
-            (2, 2) => 1,
-            (2, 3) => 4,
-            (2, 4) => 3,
-            (2, 5) => 6,
-            (2, 6) => 5,
-            (2, 7) => 8,
-            (2, 8) => 7,
-
-            (3, 2) => 4,
-            (3, 3) => 1,
-            (3, 4) => 2,
-            (3, 5) => 7,
-            (3, 6) => 8,
-            (3, 7) => 5,
-            (3, 8) => 6,
-
-            (4, 2) => 3,
-            (4, 3) => 2,
-            (4, 4) => 1,
-            (4, 5) => 8,
-            (4, 6) => 7,
-            (4, 7) => 6,
-            (4, 8) => 5,
-
-            (5, 2) => 8,
-            (5, 3) => 7,
-            (5, 4) => 6,
-            (5, 5) => 1,
-            (5, 6) => 4,
-            (5, 7) => 3,
-            (5, 8) => 2,
-
-            (6, 2) => 7,
-            (6, 3) => 8,
-            (6, 4) => 5,
-            (6, 5) => 2,
-            (6, 6) => 3,
-            (6, 7) => 4,
-            (6, 8) => 1,
-
-            (7, 2) => 6,
-            (7, 3) => 5,
-            (7, 4) => 8,
-            (7, 5) => 3,
-            (7, 6) => 2,
-            (7, 7) => 1,
-            (7, 8) => 4,
-
-            (8, 2) => 5,
-            (8, 3) => 6,
-            (8, 4) => 7,
-            (8, 5) => 4,
-            (8, 6) => 1,
-            (8, 7) => 2,
-            (8, 8) => 3,
-
-            (_, _) => 1,
-        })
-    }
-}
-impl std::ops::Neg for Orientation {
-    type Output = Self;
-    fn neg(self) -> Self::Output {
-        Self(match self.0 {
-            1 => 1,
-            2 => 2,
-            3 => 3,
-            4 => 4,
-            5 => 5,
-            6 => 8,
-            7 => 7,
-            8 => 6,
-            o => o,
-        })
-    }
-}
-impl Orientation {
-    pub const NONE: Self = Self(1);
-    pub const CW180: Self = Self(3);
-    pub const CW90: Self = Self(6);
-    pub const CW270: Self = Self(8);
-    pub const CCW90: Self = Self(8);
-
-    #[cfg(feature = "jpeg")]
-    pub fn add_to(self, mut buffer: Vec<u8>) -> Result<Vec<u8>, LibrawError> {
-        use img_parts::ImageEXIF;
-        if self.0 > 8 {
-            return Err(
-                std::io::Error::new(std::io::ErrorKind::Other, "Flip greater than 8").into(),
-            );
-        }
-
-        let mut jpeg =
-            img_parts::jpeg::Jpeg::from_bytes(img_parts::Bytes::from_iter(buffer.drain(..)))?;
-        Orientation::__remove_xmp(&mut jpeg);
-        jpeg.set_exif(Some(Self::exif_data_with_orientation(self.0).into()));
-        jpeg.encoder().write_to(&mut buffer)?;
-        Ok(buffer)
-    }
-
-    #[cfg(feature = "jpeg")]
-    fn __remove_xmp(jpeg: &mut img_parts::jpeg::Jpeg) {
-        jpeg.segments_mut().retain(|segment| {
-            !(segment.marker() == 0xe1 && segment.contents().starts_with(b"http://ns.adobe.com/"))
-        });
-    }
-
-    /// This encodes the orientation into a raw exif container data
-    #[cfg(feature = "jpeg")]
-    fn exif_data_with_orientation(o: u8) -> Vec<u8> {
-        vec![
-            0x4d, 0x4d, 0x0, 0x2a, 0x0, 0x0, 0x0, 0x8, 0x0, 0x1, 0x1, 0x12, 0x0, 0x3, 0x0, 0x0,
-            0x0, 0x1, 0x0, o, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
-        ]
-    }
-}
-
-/// libraw_data_t.sizes.flip
-/// Possible values 0, 3, 5, 6
-#[derive(Debug, Eq, PartialEq)]
-pub struct Flip(pub i32);
-impl Flip {
-    pub const NONE: Self = Self(0);
-    pub const CW180: Self = Self(3);
-    pub const CW90: Self = Self(6);
-    pub const CW270: Self = Self(5);
-    pub const CCW90: Self = Self(5);
-}
-
-impl From<i32> for Flip {
-    fn from(flip: i32) -> Self {
-        Self(flip)
-    }
-}
-
-impl From<Flip> for Orientation {
-    fn from(flip: Flip) -> Self {
-        match flip {
-            Flip::NONE => Orientation::NONE,
-            Flip::CW90 => Orientation::CW90,
-            Flip::CW180 => Orientation::CW180,
-            Flip::CW270 => Orientation::CW270,
-            _ => Orientation::NONE,
-        }
-    }
-}
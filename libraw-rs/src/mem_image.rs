@@ -0,0 +1,182 @@
+//! Copy the processed image into caller provided buffers
+//!
+//! [`Processor::copy_into`] writes straight into the buffer with libraw's copy_mem_image
+//! (through `shim/mem_image.cpp`) instead of allocating a `libraw_processed_image_t` like
+//! [`Processor::dcraw_process_make_mem_image`].
+
+use core::ffi::{c_int, c_void};
+
+use crate::error::InternalLibrawError;
+use crate::{LibrawError, Processor};
+
+extern "C" {
+    fn libraw_rs_get_mem_image_format(
+        lr: *mut sys::libraw_data_t,
+        width: *mut c_int,
+        height: *mut c_int,
+        colors: *mut c_int,
+        bps: *mut c_int,
+    );
+    fn libraw_rs_copy_mem_image(
+        lr: *mut sys::libraw_data_t,
+        scan0: *mut c_void,
+        stride: c_int,
+        bgr: c_int,
+    ) -> c_int;
+}
+
+/// Size and sample format of the processed image
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MemImageFormat {
+    pub width: u32,
+    pub height: u32,
+    pub colors: u16,
+    /// Bits per sample, 8 or 16
+    pub bits: u16,
+}
+
+impl MemImageFormat {
+    pub fn bytes_per_sample(&self) -> usize {
+        if self.bits > 8 {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// Length in bytes of a row in `layout` without padding
+    pub fn row_len(&self, layout: PixelLayout) -> usize {
+        self.width as usize * layout.channels() * self.bytes_per_sample()
+    }
+
+    /// Length in bytes of a buffer with rows of `stride` bytes
+    ///
+    /// The last row doesn't need the padding
+    pub fn buffer_len(&self, layout: PixelLayout, stride: usize) -> usize {
+        match self.height as usize {
+            0 => 0,
+            height => stride * (height - 1) + self.row_len(layout),
+        }
+    }
+}
+
+/// Order of the channels in the buffer of [`Processor::copy_into`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PixelLayout {
+    #[default]
+    Rgb,
+    Bgr,
+    /// Opaque alpha
+    Rgba,
+    /// Opaque alpha
+    Bgra,
+}
+
+impl PixelLayout {
+    pub fn channels(&self) -> usize {
+        match self {
+            PixelLayout::Rgb | PixelLayout::Bgr => 3,
+            PixelLayout::Rgba | PixelLayout::Bgra => 4,
+        }
+    }
+
+    fn is_bgr(&self) -> bool {
+        matches!(self, PixelLayout::Bgr | PixelLayout::Bgra)
+    }
+}
+
+impl Processor {
+    /// The format of the image [`Processor::copy_into`] /
+    /// [`Processor::dcraw_process_make_mem_image`] produce (libraw's get_mem_image_format)
+    ///
+    /// Only accurate after `dcraw_process`, the size depends on the processing options (half
+    /// size, flip, fuji rotation, pixel aspect)
+    pub fn output_format(&self) -> MemImageFormat {
+        let (mut width, mut height, mut colors, mut bps) = (0, 0, 0, 0);
+        unsafe {
+            libraw_rs_get_mem_image_format(
+                self.inner.as_ptr(),
+                &mut width,
+                &mut height,
+                &mut colors,
+                &mut bps,
+            )
+        };
+        MemImageFormat {
+            width: width.max(0) as u32,
+            height: height.max(0) as u32,
+            colors: colors.max(0) as u16,
+            bits: bps.max(0) as u16,
+        }
+    }
+
+    /// Copy the processed image into `buffer` (libraw's copy_mem_image)
+    ///
+    /// Unlike [`Processor::render`] this doesn't process, call `dcraw_process` first (the size of
+    /// the image is only known afterwards) or it returns
+    /// [`InternalLibrawError::OutOfOrderCall`].
+    ///
+    /// Rows start every `stride` bytes, see [`MemImageFormat::buffer_len`] for the length the
+    /// buffer needs. 16 bit samples are written in native endian and need a 2 byte aligned
+    /// buffer and stride.
+    pub fn copy_into(
+        &mut self,
+        buffer: &mut [u8],
+        stride: usize,
+        layout: PixelLayout,
+    ) -> Result<MemImageFormat, LibrawError> {
+        let progress = unsafe { self.inner.as_ref().progress_flags };
+        if progress & sys::LibRaw_progress_LIBRAW_PROGRESS_CONVERT_RGB == 0 {
+            return Err(InternalLibrawError::OutOfOrderCall.into());
+        }
+        let format = self.output_format();
+        if format.width == 0 || format.height == 0 {
+            return Err(InternalLibrawError::OutOfOrderCall.into());
+        }
+        if format.colors != 3 {
+            return Err(LibrawError::InvalidOutputBuffer(
+                "only 3 color images can be copied into a pixel layout",
+            ));
+        }
+        let bytes = format.bytes_per_sample();
+        let row_len = format.row_len(layout);
+        if stride < row_len {
+            return Err(LibrawError::InvalidOutputBuffer(
+                "stride is shorter than a row",
+            ));
+        }
+        if buffer.len() < format.buffer_len(layout, stride) {
+            return Err(LibrawError::InvalidOutputBuffer("buffer is too small"));
+        }
+        if bytes == 2 && (buffer.as_ptr() as usize % 2 != 0 || stride % 2 != 0) {
+            return Err(LibrawError::InvalidOutputBuffer(
+                "16 bit output needs a 2 byte aligned buffer and stride",
+            ));
+        }
+        let c_stride = c_int::try_from(stride)
+            .map_err(|_| LibrawError::InvalidOutputBuffer("stride is too large"))?;
+
+        LibrawError::check(unsafe {
+            libraw_rs_copy_mem_image(
+                self.inner.as_ptr(),
+                buffer.as_mut_ptr() as *mut c_void,
+                c_stride,
+                layout.is_bgr() as c_int,
+            )
+        })?;
+
+        if layout.channels() == 4 {
+            // libraw wrote packed 3 channel rows, spread them out from the end so nothing is
+            // overwritten before it's moved
+            let pixel = 3 * bytes;
+            for row in buffer.chunks_mut(stride).take(format.height as usize) {
+                for col in (0..format.width as usize).rev() {
+                    row.copy_within(col * pixel..(col + 1) * pixel, col * (pixel + bytes));
+                    let alpha = col * (pixel + bytes) + pixel;
+                    row[alpha..alpha + bytes].fill(0xff);
+                }
+            }
+        }
+        Ok(format)
+    }
+}
@@ -0,0 +1,373 @@
+//! Owned snapshot of the metadata libraw parses while opening a file
+//!
+//! All the accessors on [`Processor`] (`idata`, `sizes`, `imgother`, ...) return references to the
+//! raw bindgen structs which are only valid as long as the processor is alive and not recycled.
+//! [`Metadata`] copies everything into plain rust types so it can outlive the processor and be
+//! sent to other threads.
+
+use std::time::{Duration, SystemTime};
+
+use crate::traits::LRString;
+use crate::{Flip, Orientation, Processor};
+
+/// Everything libraw knows about the currently opened file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metadata {
+    pub camera: CameraInfo,
+    pub capture: CaptureInfo,
+    pub lens: LensInfo,
+    pub sizes: ImageSizes,
+    pub shooting: ShootingInfo,
+    pub color: ColorInfo,
+}
+
+/// From libraw_iparams_t and libraw_shootinginfo_t
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CameraInfo {
+    pub make: String,
+    pub model: String,
+    pub normalized_make: String,
+    pub normalized_model: String,
+    pub software: Option<String>,
+    pub body_serial: Option<String>,
+    pub internal_body_serial: Option<String>,
+    pub firmware: Option<String>,
+    /// DNG version of the file, [`None`] if the file is not a DNG
+    pub dng_version: Option<u32>,
+    pub is_foveon: bool,
+    /// Number of raw images in the file
+    pub raw_count: u32,
+    /// Number of colors in the color filter array
+    pub colors: i32,
+    /// Description of the colors ( eg. "RGBG" )
+    pub color_description: String,
+    /// The bit mask describing the layout of the color filter array
+    pub filters: u32,
+}
+
+/// From libraw_imgother_t
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptureInfo {
+    pub iso: Option<f32>,
+    pub shutter: Option<Duration>,
+    pub aperture: Option<f32>,
+    pub focal_length: Option<f32>,
+    pub timestamp: Option<SystemTime>,
+    pub shot_order: u32,
+    pub description: Option<String>,
+    pub artist: Option<String>,
+    pub gps: Option<GpsInfo>,
+    pub flash_used: bool,
+}
+
+/// From libraw_gps_info_t
+///
+/// Coordinates are stored as (degrees, minutes, seconds) just like in exif
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GpsInfo {
+    pub latitude: [f32; 3],
+    /// 'N' or 'S'
+    pub latitude_ref: char,
+    pub longitude: [f32; 3],
+    /// 'E' or 'W'
+    pub longitude_ref: char,
+    /// Altitude in meters, always positive, check `below_sea_level`
+    pub altitude: f32,
+    pub below_sea_level: bool,
+    /// UTC time of the fix as (hours, minutes, seconds)
+    pub timestamp: [f32; 3],
+    /// 'A' for active or 'V' for void
+    pub status: char,
+}
+
+impl GpsInfo {
+    /// Latitude in signed decimal degrees (south is negative)
+    pub fn latitude_degrees(&self) -> f64 {
+        let degrees = dms_to_degrees(self.latitude);
+        if self.latitude_ref == 'S' {
+            -degrees
+        } else {
+            degrees
+        }
+    }
+
+    /// Longitude in signed decimal degrees (west is negative)
+    pub fn longitude_degrees(&self) -> f64 {
+        let degrees = dms_to_degrees(self.longitude);
+        if self.longitude_ref == 'W' {
+            -degrees
+        } else {
+            degrees
+        }
+    }
+
+    /// Altitude in meters, negative if below sea level
+    pub fn altitude_meters(&self) -> f32 {
+        if self.below_sea_level {
+            -self.altitude
+        } else {
+            self.altitude
+        }
+    }
+}
+
+fn dms_to_degrees(dms: [f32; 3]) -> f64 {
+    dms[0] as f64 + dms[1] as f64 / 60.0 + dms[2] as f64 / 3600.0
+}
+
+/// From libraw_lensinfo_t
+#[derive(Debug, Clone, PartialEq)]
+pub struct LensInfo {
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub serial: Option<String>,
+    pub internal_serial: Option<String>,
+    pub min_focal: Option<f32>,
+    pub max_focal: Option<f32>,
+    pub max_aperture_at_min_focal: Option<f32>,
+    pub max_aperture_at_max_focal: Option<f32>,
+    /// Maximum aperture as written in the exif
+    pub exif_max_aperture: Option<f32>,
+    pub focal_length_in_35mm: Option<u16>,
+    /// Lens id from the makernotes, [`None`] if unknown
+    pub id: Option<u64>,
+}
+
+/// From libraw_image_sizes_t
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImageSizes {
+    pub raw_width: u16,
+    pub raw_height: u16,
+    pub width: u16,
+    pub height: u16,
+    pub top_margin: u16,
+    pub left_margin: u16,
+    pub iwidth: u16,
+    pub iheight: u16,
+    /// Length of a row of the raw data in bytes
+    pub raw_pitch: u32,
+    pub pixel_aspect: f64,
+    pub flip: Flip,
+}
+
+impl ImageSizes {
+    /// The orientation of the image as an exif orientation
+    pub fn orientation(&self) -> Orientation {
+        Orientation::from(self.flip)
+    }
+}
+
+/// From libraw_shootinginfo_t
+///
+/// The values are vendor specific and [`None`] if the camera didn't record them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShootingInfo {
+    pub drive_mode: Option<i16>,
+    pub focus_mode: Option<i16>,
+    pub metering_mode: Option<i16>,
+    pub af_point: Option<i16>,
+    pub exposure_mode: Option<i16>,
+    pub exposure_program: Option<i16>,
+    pub image_stabilization: Option<i16>,
+}
+
+/// From libraw_colordata_t
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorInfo {
+    pub black: u32,
+    pub maximum: u32,
+    pub data_maximum: u32,
+    pub cam_mul: [f32; 4],
+    pub pre_mul: [f32; 4],
+    pub rgb_cam: [[f32; 4]; 3],
+    pub cam_xyz: [[f32; 3]; 4],
+    /// Number of significant bits in the raw data
+    pub raw_bps: u32,
+    pub color_space: ColorSpace,
+    pub unique_camera_model: Option<String>,
+    pub image_unique_id: Option<String>,
+    pub original_raw_file_name: Option<String>,
+}
+
+/// The color space recorded in the exif / makernotes of the file
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    NotFound,
+    Srgb,
+    AdobeRgb,
+    WideGamutRgb,
+    ProPhotoRgb,
+    Icc,
+    Uncalibrated,
+    CameraLinearUniWb,
+    CameraLinear,
+    CameraGammaUniWb,
+    CameraGamma,
+    MonochromeLinear,
+    MonochromeGamma,
+    Unknown,
+}
+
+impl From<sys::LibRaw_colorspace> for ColorSpace {
+    fn from(value: sys::LibRaw_colorspace) -> Self {
+        use ColorSpace::*;
+        match value {
+            sys::LibRaw_colorspace_LIBRAW_COLORSPACE_NotFound => NotFound,
+            sys::LibRaw_colorspace_LIBRAW_COLORSPACE_sRGB => Srgb,
+            sys::LibRaw_colorspace_LIBRAW_COLORSPACE_AdobeRGB => AdobeRgb,
+            sys::LibRaw_colorspace_LIBRAW_COLORSPACE_WideGamutRGB => WideGamutRgb,
+            sys::LibRaw_colorspace_LIBRAW_COLORSPACE_ProPhotoRGB => ProPhotoRgb,
+            sys::LibRaw_colorspace_LIBRAW_COLORSPACE_ICC => Icc,
+            sys::LibRaw_colorspace_LIBRAW_COLORSPACE_Uncalibrated => Uncalibrated,
+            sys::LibRaw_colorspace_LIBRAW_COLORSPACE_CameraLinearUniWB => CameraLinearUniWb,
+            sys::LibRaw_colorspace_LIBRAW_COLORSPACE_CameraLinear => CameraLinear,
+            sys::LibRaw_colorspace_LIBRAW_COLORSPACE_CameraGammaUniWB => CameraGammaUniWb,
+            sys::LibRaw_colorspace_LIBRAW_COLORSPACE_CameraGamma => CameraGamma,
+            sys::LibRaw_colorspace_LIBRAW_COLORSPACE_MonochromeLinear => MonochromeLinear,
+            sys::LibRaw_colorspace_LIBRAW_COLORSPACE_MonochromeGamma => MonochromeGamma,
+            _ => Unknown,
+        }
+    }
+}
+
+/// Returns None for empty strings
+fn non_empty(s: &str) -> Option<String> {
+    let s = s.trim();
+    (!s.is_empty()).then(|| s.to_owned())
+}
+
+/// libraw uses 0 for values it couldn't find
+fn positive(v: f32) -> Option<f32> {
+    (v.is_finite() && v > 0.0).then_some(v)
+}
+
+/// libraw uses -1 for values it couldn't find in shootinginfo
+fn known(v: i16) -> Option<i16> {
+    (v >= 0).then_some(v)
+}
+
+fn c_char(c: libc::c_char) -> char {
+    c as u8 as char
+}
+
+impl Processor {
+    /// Copies all the metadata parsed by libraw into an owned [`Metadata`]
+    ///
+    /// Call this after `open`
+    pub fn metadata(&self) -> Metadata {
+        let idata = self.idata();
+        let other = self.imgother();
+        let lens = self.lensinfo();
+        let sizes = self.sizes();
+        let shooting = self.shootinginfo();
+        let color = self.color();
+        let common = &self.makernotes().common;
+
+        let camera = CameraInfo {
+            make: idata.make.as_ascii().to_owned(),
+            model: idata.model.as_ascii().to_owned(),
+            normalized_make: idata.normalized_make.as_ascii().to_owned(),
+            normalized_model: idata.normalized_model.as_ascii().to_owned(),
+            software: non_empty(idata.software.as_ascii()),
+            body_serial: non_empty(shooting.BodySerial.as_ascii()),
+            internal_body_serial: non_empty(shooting.InternalBodySerial.as_ascii()),
+            firmware: non_empty(common.firmware.as_ascii()),
+            dng_version: (idata.dng_version != 0).then_some(idata.dng_version),
+            is_foveon: idata.is_foveon != 0,
+            raw_count: idata.raw_count,
+            colors: idata.colors,
+            color_description: idata.cdesc.as_ascii().to_owned(),
+            filters: idata.filters,
+        };
+
+        let gps = &other.parsed_gps;
+        let capture = CaptureInfo {
+            iso: positive(other.iso_speed),
+            shutter: positive(other.shutter).and_then(|s| Duration::try_from_secs_f32(s).ok()),
+            aperture: positive(other.aperture),
+            focal_length: positive(other.focal_len),
+            timestamp: (other.timestamp > 0)
+                .then(|| SystemTime::UNIX_EPOCH + Duration::from_secs(other.timestamp as u64)),
+            shot_order: other.shot_order,
+            description: non_empty(other.desc.as_ascii()),
+            artist: non_empty(other.artist.as_ascii()),
+            gps: (gps.gpsparsed != 0).then(|| GpsInfo {
+                latitude: gps.latitude,
+                latitude_ref: c_char(gps.latref),
+                longitude: gps.longitude,
+                longitude_ref: c_char(gps.longref),
+                altitude: gps.altitude,
+                below_sea_level: gps.altref == 1,
+                timestamp: gps.gpstimestamp,
+                status: c_char(gps.gpsstatus),
+            }),
+            flash_used: color.flash_used > 0.0,
+        };
+
+        let lens = LensInfo {
+            make: non_empty(lens.LensMake.as_ascii()),
+            model: non_empty(lens.Lens.as_ascii())
+                .or_else(|| non_empty(lens.makernotes.Lens.as_ascii())),
+            serial: non_empty(lens.LensSerial.as_ascii()),
+            internal_serial: non_empty(lens.InternalLensSerial.as_ascii()),
+            min_focal: positive(lens.MinFocal),
+            max_focal: positive(lens.MaxFocal),
+            max_aperture_at_min_focal: positive(lens.MaxAp4MinFocal),
+            max_aperture_at_max_focal: positive(lens.MaxAp4MaxFocal),
+            exif_max_aperture: positive(lens.EXIF_MaxAp),
+            focal_length_in_35mm: (lens.FocalLengthIn35mmFormat != 0)
+                .then_some(lens.FocalLengthIn35mmFormat),
+            // libraw uses 0xffffffffffffffff for unknown lens ids
+            id: (lens.makernotes.LensID != u64::MAX).then_some(lens.makernotes.LensID),
+        };
+
+        let sizes = ImageSizes {
+            raw_width: sizes.raw_width,
+            raw_height: sizes.raw_height,
+            width: sizes.width,
+            height: sizes.height,
+            top_margin: sizes.top_margin,
+            left_margin: sizes.left_margin,
+            iwidth: sizes.iwidth,
+            iheight: sizes.iheight,
+            raw_pitch: sizes.raw_pitch,
+            pixel_aspect: sizes.pixel_aspect,
+            flip: Flip::from(sizes.flip),
+        };
+
+        let shooting = ShootingInfo {
+            drive_mode: known(shooting.DriveMode),
+            focus_mode: known(shooting.FocusMode),
+            metering_mode: known(shooting.MeteringMode),
+            af_point: known(shooting.AFPoint),
+            exposure_mode: known(shooting.ExposureMode),
+            exposure_program: known(shooting.ExposureProgram),
+            image_stabilization: known(shooting.ImageStabilization),
+        };
+
+        let color = ColorInfo {
+            black: color.black,
+            maximum: color.maximum,
+            data_maximum: color.data_maximum,
+            cam_mul: color.cam_mul,
+            pre_mul: color.pre_mul,
+            rgb_cam: color.rgb_cam,
+            cam_xyz: color.cam_xyz,
+            raw_bps: color.raw_bps,
+            color_space: ColorSpace::from(color.ExifColorSpace as sys::LibRaw_colorspace),
+            unique_camera_model: non_empty(color.UniqueCameraModel.as_ascii()),
+            image_unique_id: non_empty(color.ImageUniqueID.as_ascii()),
+            original_raw_file_name: non_empty(color.OriginalRawFileName.as_ascii()),
+        };
+
+        Metadata {
+            camera,
+            capture,
+            lens,
+            sizes,
+            shooting,
+            color,
+        }
+    }
+}
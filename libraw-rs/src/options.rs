@@ -0,0 +1,721 @@
+//! Typed processing options (libraw_output_params_t)
+//!
+//! [`ProcessingOptions`] covers everything [`crate::Params`] does with enums instead of bare
+//! integers, owns the profile / pixel map paths and is validated before anything is written to
+//! libraw.
+
+use std::ffi::{CStr, CString};
+use std::path::PathBuf;
+
+use crate::{path_to_cstr, sys, Flip, LibrawError, Processor};
+
+/// Demosaicing algorithm (user_qual)
+#[non_exhaustive]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Demosaic {
+    Linear,
+    Vng,
+    Ppg,
+    /// X-Trans sensors always use Markesteijn, with 3 passes for AHD and the algorithms after it
+    /// and 1 pass otherwise
+    #[default]
+    Ahd,
+    Dcb,
+    Dht,
+    /// Modified AHD
+    Aahd,
+}
+
+impl Demosaic {
+    fn to_libraw(self) -> i32 {
+        match self {
+            Demosaic::Linear => 0,
+            Demosaic::Vng => 1,
+            Demosaic::Ppg => 2,
+            Demosaic::Ahd => 3,
+            Demosaic::Dcb => 4,
+            Demosaic::Dht => 11,
+            Demosaic::Aahd => 12,
+        }
+    }
+
+    fn from_libraw(value: i32) -> Self {
+        match value {
+            0 => Demosaic::Linear,
+            1 => Demosaic::Vng,
+            2 => Demosaic::Ppg,
+            4 => Demosaic::Dcb,
+            11 => Demosaic::Dht,
+            12 => Demosaic::Aahd,
+            // -1 and the algorithms which aren't built in fall back to AHD
+            _ => Demosaic::Ahd,
+        }
+    }
+}
+
+/// Highlight recovery (highlight)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum HighlightMode {
+    /// Clip the highlights to solid white
+    #[default]
+    Clip,
+    /// Leave the highlights unclipped in various shades of pink
+    Unclip,
+    /// Blend clipped and unclipped values for a gradual fade to white
+    Blend,
+    /// Reconstruct the highlights, 3 favours whites and 9 favours colors
+    Rebuild(u8),
+}
+
+impl HighlightMode {
+    fn to_libraw(self) -> i32 {
+        match self {
+            HighlightMode::Clip => 0,
+            HighlightMode::Unclip => 1,
+            HighlightMode::Blend => 2,
+            HighlightMode::Rebuild(level) => level as i32,
+        }
+    }
+
+    fn from_libraw(value: i32) -> Self {
+        match value {
+            1 => HighlightMode::Unclip,
+            2 => HighlightMode::Blend,
+            3..=9 => HighlightMode::Rebuild(value as u8),
+            _ => HighlightMode::Clip,
+        }
+    }
+}
+
+/// Color space of the output (output_color)
+#[non_exhaustive]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum OutputColorSpace {
+    /// Camera color space, no conversion
+    Raw,
+    #[default]
+    Srgb,
+    AdobeRgb,
+    WideGamut,
+    ProPhoto,
+    Xyz,
+    Aces,
+}
+
+impl OutputColorSpace {
+    fn to_libraw(self) -> i32 {
+        match self {
+            OutputColorSpace::Raw => 0,
+            OutputColorSpace::Srgb => 1,
+            OutputColorSpace::AdobeRgb => 2,
+            OutputColorSpace::WideGamut => 3,
+            OutputColorSpace::ProPhoto => 4,
+            OutputColorSpace::Xyz => 5,
+            OutputColorSpace::Aces => 6,
+        }
+    }
+
+    fn from_libraw(value: i32) -> Self {
+        match value {
+            0 => OutputColorSpace::Raw,
+            2 => OutputColorSpace::AdobeRgb,
+            3 => OutputColorSpace::WideGamut,
+            4 => OutputColorSpace::ProPhoto,
+            5 => OutputColorSpace::Xyz,
+            6 => OutputColorSpace::Aces,
+            _ => OutputColorSpace::Srgb,
+        }
+    }
+}
+
+/// Gamma curve of the output (gamm[0], gamm[1])
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Gamma {
+    /// Power 2.222 with a toe slope of 4.5, the libraw default
+    #[default]
+    Bt709,
+    /// Power 2.4 with a toe slope of 12.92
+    Srgb,
+    Linear,
+    Custom {
+        /// Inverse of gamm[0]
+        power: f64,
+        /// Toe slope, 0 for a pure power curve
+        slope: f64,
+    },
+}
+
+impl Gamma {
+    /// (power, slope)
+    pub fn curve(&self) -> (f64, f64) {
+        match *self {
+            Gamma::Bt709 => (1.0 / 0.45, 4.5),
+            Gamma::Srgb => (2.4, 12.92),
+            Gamma::Linear => (1.0, 1.0),
+            Gamma::Custom { power, slope } => (power, slope),
+        }
+    }
+
+    fn from_libraw(gamm: &[f64; 6]) -> Self {
+        let (power, slope) = (1.0 / gamm[0], gamm[1]);
+        [Gamma::Bt709, Gamma::Srgb, Gamma::Linear]
+            .into_iter()
+            .find(|preset| {
+                let (p, s) = preset.curve();
+                (p - power).abs() < 1e-6 && (s - slope).abs() < 1e-6
+            })
+            .unwrap_or(Gamma::Custom { power, slope })
+    }
+}
+
+/// White balance (use_auto_wb, use_camera_wb, user_mul, greybox)
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum WhiteBalance {
+    /// Daylight multipliers (pre_mul)
+    #[default]
+    Daylight,
+    /// Average the whole image
+    Auto,
+    /// Average a region of the image
+    AutoRegion {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+    /// As shot, falls back to daylight if the camera didn't record it
+    Camera,
+    /// As shot, falls back to auto if the camera didn't record it
+    CameraOrAuto,
+    /// Custom multipliers for R, G, B, G2
+    Custom([f32; 4]),
+}
+
+/// When to use the color matrix embedded in the file (use_camera_matrix)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum CameraMatrix {
+    Never,
+    /// For DNG files and when the camera white balance is used
+    #[default]
+    Default,
+    Always,
+}
+
+/// Bits per sample of the output (output_bps)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum BitDepth {
+    #[default]
+    Eight,
+    Sixteen,
+}
+
+/// FBDD noise reduction before demosaicing (fbdd_noiserd)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum FbddNoiseReduction {
+    #[default]
+    Off,
+    Light,
+    Full,
+}
+
+/// Exposure correction before demosaicing (exp_correc, exp_shift, exp_preser)
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExposureCorrection {
+    /// Linear shift, 0.25 (2 stops darker) to 8.0 (3 stops lighter)
+    pub shift: f32,
+    /// Highlight preservation when lightening, 0.0 to 1.0
+    pub preserve_highlights: f32,
+}
+
+/// The input profile (camera_profile)
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum CameraProfile {
+    /// The ICC profile embedded in the file
+    Embedded,
+    Path(PathBuf),
+}
+
+/// libraw_output_params_t with typed values
+///
+/// The defaults are the same as libraw's
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct ProcessingOptions {
+    pub demosaic: Demosaic,
+    pub half_size: bool,
+    /// Interpolate RGB as four colors (four_color_rgb)
+    pub four_color_rgb: bool,
+    pub highlight: HighlightMode,
+    pub white_balance: WhiteBalance,
+    pub camera_matrix: CameraMatrix,
+    pub output_color: OutputColorSpace,
+    pub output_bps: BitDepth,
+    pub gamma: Gamma,
+    /// Brightness multiplier (bright)
+    pub brightness: f32,
+    /// Brighten the image so that `auto_bright_threshold` of the pixels are clipped
+    pub auto_brightness: bool,
+    pub auto_bright_threshold: f32,
+    /// Lower the maximum to the largest value in the data if it's above this portion of the
+    /// maximum, 0 to disable
+    pub adjust_maximum_threshold: f32,
+    /// Wavelet denoising threshold (threshold), 0 to disable
+    pub noise_threshold: f32,
+    /// Passes of 3x3 median filter on R-G and B-G (med_passes)
+    pub median_passes: u32,
+    /// Scale of the red and blue layers to correct chromatic aberration (aber)
+    pub chromatic_aberration: [f64; 2],
+    /// Only process this area (x, y, width, height) of the image (cropbox)
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub crop: Option<[u32; 4]>,
+    /// Override the flip of the file (user_flip)
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub user_flip: Option<Flip>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub user_black: Option<i32>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "cblack", skip_serializing_if = "cblack::is_unset")
+    )]
+    pub user_cblack: [Option<i32>; 4],
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub user_saturation: Option<i32>,
+    /// Rotate fuji images by 45 degrees (use_fuji_rotate)
+    pub use_fuji_rotate: bool,
+    pub green_matching: bool,
+    /// Number of DCB correction passes, [`None`] for the default
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub dcb_iterations: Option<u32>,
+    pub dcb_enhance: bool,
+    pub fbdd_noise_reduction: FbddNoiseReduction,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub exposure: Option<ExposureCorrection>,
+    pub no_auto_scale: bool,
+    pub no_interpolation: bool,
+    /// Write tiff instead of ppm in `dcraw_ppm_tiff_writer` (output_tiff)
+    pub output_tiff: bool,
+    /// ICC profile of the output, overrides `output_color` (needs libraw built with LCMS, see
+    /// [`LCMS`])
+    ///
+    /// The encoded images embed this file when libraw uses it, see
+    /// [`crate::icc::IccProfile::Auto`]
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub output_profile: Option<PathBuf>,
+    /// ICC profile of the camera (needs libraw built with LCMS, see [`LCMS`])
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub camera_profile: Option<CameraProfile>,
+    /// List of dead pixels in dcraw's format
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub bad_pixels: Option<PathBuf>,
+    /// 16 bit pgm dark frame to subtract
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub dark_frame: Option<PathBuf>,
+}
+
+impl Default for ProcessingOptions {
+    fn default() -> Self {
+        Self {
+            demosaic: Demosaic::default(),
+            half_size: false,
+            four_color_rgb: false,
+            highlight: HighlightMode::default(),
+            white_balance: WhiteBalance::default(),
+            camera_matrix: CameraMatrix::default(),
+            output_color: OutputColorSpace::default(),
+            output_bps: BitDepth::default(),
+            gamma: Gamma::default(),
+            brightness: 1.0,
+            auto_brightness: true,
+            auto_bright_threshold: 0.01,
+            adjust_maximum_threshold: 0.75,
+            noise_threshold: 0.0,
+            median_passes: 0,
+            chromatic_aberration: [1.0, 1.0],
+            crop: None,
+            user_flip: None,
+            user_black: None,
+            user_cblack: [None; 4],
+            user_saturation: None,
+            use_fuji_rotate: true,
+            green_matching: false,
+            dcb_iterations: None,
+            dcb_enhance: false,
+            fbdd_noise_reduction: FbddNoiseReduction::default(),
+            exposure: None,
+            no_auto_scale: false,
+            no_interpolation: false,
+            output_tiff: false,
+            output_profile: None,
+            camera_profile: None,
+            bad_pixels: None,
+            dark_frame: None,
+        }
+    }
+}
+
+/// user_cblack as a table with optional `r`, `g`, `b` and `g2` values
+#[cfg(feature = "serde")]
+mod cblack {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Default, Serialize, Deserialize)]
+    #[serde(default)]
+    struct Channels {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        r: Option<i32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        g: Option<i32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        b: Option<i32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        g2: Option<i32>,
+    }
+
+    pub fn is_unset(cblack: &[Option<i32>; 4]) -> bool {
+        cblack.iter().all(Option::is_none)
+    }
+
+    pub fn serialize<S: Serializer>(cblack: &[Option<i32>; 4], s: S) -> Result<S::Ok, S::Error> {
+        let [r, g, b, g2] = *cblack;
+        Channels { r, g, b, g2 }.serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<[Option<i32>; 4], D::Error> {
+        let Channels { r, g, b, g2 } = Channels::deserialize(d)?;
+        Ok([r, g, b, g2])
+    }
+}
+
+/// libraw's marker for an unset user_cblack
+const CBLACK_UNSET: i32 = -1000001;
+/// greybox / cropbox covering the whole image
+const WHOLE_IMAGE: [u32; 4] = [0, 0, u32::MAX, u32::MAX];
+
+/// Whether libraw is built with LCMS, libraw-sys never enables it so libraw ignores the ICC
+/// profile options
+pub const LCMS: bool = false;
+
+impl ProcessingOptions {
+    /// Check the values which libraw would silently misbehave on
+    pub fn validate(&self) -> Result<(), LibrawError> {
+        let invalid = |condition: bool, message| {
+            if condition {
+                Err(LibrawError::InvalidOption(message))
+            } else {
+                Ok(())
+            }
+        };
+        let positive = |v: f64| v.is_finite() && v > 0.0;
+        if let HighlightMode::Rebuild(level) = self.highlight {
+            invalid(
+                !(3..=9).contains(&level),
+                "highlight rebuild level must be between 3 and 9",
+            )?;
+        }
+        match self.white_balance {
+            WhiteBalance::Custom(mul) => invalid(
+                mul.iter().any(|m| !m.is_finite() || *m < 0.0) || mul.iter().all(|m| *m == 0.0),
+                "white balance multipliers must be positive",
+            )?,
+            WhiteBalance::AutoRegion { width, height, .. } => invalid(
+                width == 0 || height == 0,
+                "white balance region must not be empty",
+            )?,
+            _ => (),
+        }
+        let (power, slope) = self.gamma.curve();
+        invalid(
+            !positive(power) || !slope.is_finite() || slope < 0.0,
+            "gamma power must be positive and the slope must not be negative",
+        )?;
+        invalid(
+            !positive(self.brightness as f64),
+            "brightness must be positive",
+        )?;
+        invalid(
+            !(0.0..1.0).contains(&self.auto_bright_threshold),
+            "auto bright threshold must be between 0 and 1",
+        )?;
+        invalid(
+            !(0.0..=1.0).contains(&self.adjust_maximum_threshold),
+            "adjust maximum threshold must be between 0 and 1",
+        )?;
+        invalid(
+            !self.noise_threshold.is_finite() || self.noise_threshold < 0.0,
+            "noise threshold must not be negative",
+        )?;
+        invalid(
+            !self.chromatic_aberration.iter().all(|a| positive(*a)),
+            "chromatic aberration scales must be positive",
+        )?;
+        if let Some([_, _, width, height]) = self.crop {
+            invalid(width == 0 || height == 0, "crop must not be empty")?;
+        }
+        if let Some(flip) = self.user_flip {
+            invalid(
+                !(0..=7).contains(&flip.0),
+                "user flip must be between 0 and 7",
+            )?;
+        }
+        invalid(
+            !LCMS && self.output_profile.is_some(),
+            "output profile needs libraw built with LCMS",
+        )?;
+        invalid(
+            !LCMS && self.camera_profile.is_some(),
+            "camera profile needs libraw built with LCMS",
+        )?;
+        invalid(
+            self.median_passes > i32::MAX as u32,
+            "too many median passes",
+        )?;
+        invalid(
+            self.dcb_iterations.map_or(false, |i| i > i32::MAX as u32),
+            "too many dcb iterations",
+        )?;
+        if let Some(exposure) = self.exposure {
+            invalid(
+                !(0.25..=8.0).contains(&exposure.shift),
+                "exposure shift must be between 0.25 and 8",
+            )?;
+            invalid(
+                !(0.0..=1.0).contains(&exposure.preserve_highlights),
+                "highlight preservation must be between 0 and 1",
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn cstr_to_path(ptr: *const libc::c_char) -> Option<PathBuf> {
+    use std::os::unix::ffi::OsStrExt;
+    if ptr.is_null() {
+        return None;
+    }
+    let bytes = unsafe { CStr::from_ptr(ptr) }.to_bytes();
+    Some(PathBuf::from(std::ffi::OsStr::from_bytes(bytes)))
+}
+#[cfg(windows)]
+fn cstr_to_path(ptr: *const libc::c_char) -> Option<PathBuf> {
+    if ptr.is_null() {
+        return None;
+    }
+    let path = unsafe { CStr::from_ptr(ptr) }.to_string_lossy();
+    Some(PathBuf::from(path.into_owned()))
+}
+
+/// The path strings libraw points to, they have to outlive the params
+pub(crate) type OptionStrings = [Option<CString>; 4];
+
+/// Validate `options` and write them to `params`
+pub(crate) fn write_options(
+    params: &mut sys::libraw_output_params_t,
+    options: &ProcessingOptions,
+) -> Result<OptionStrings, LibrawError> {
+    options.validate()?;
+    let cstr = |path: &Option<PathBuf>| path.as_ref().map(path_to_cstr).transpose();
+    let strings = [
+        cstr(&options.output_profile)?,
+        match &options.camera_profile {
+            Some(CameraProfile::Embedded) => Some(CString::new("embed")?),
+            Some(CameraProfile::Path(path)) => Some(path_to_cstr(path)?),
+            None => None,
+        },
+        cstr(&options.bad_pixels)?,
+        cstr(&options.dark_frame)?,
+    ];
+    let ptr = |s: &Option<CString>| {
+        s.as_ref()
+            .map_or(core::ptr::null_mut(), |s| s.as_ptr() as *mut libc::c_char)
+    };
+
+    params.user_qual = options.demosaic.to_libraw();
+    params.half_size = options.half_size as i32;
+    params.four_color_rgb = options.four_color_rgb as i32;
+    params.highlight = options.highlight.to_libraw();
+
+    params.use_auto_wb = 0;
+    params.use_camera_wb = 0;
+    params.user_mul = [0.0; 4];
+    params.greybox = WHOLE_IMAGE;
+    match options.white_balance {
+        WhiteBalance::Daylight => (),
+        WhiteBalance::Auto => params.use_auto_wb = 1,
+        WhiteBalance::AutoRegion {
+            x,
+            y,
+            width,
+            height,
+        } => {
+            params.use_auto_wb = 1;
+            params.greybox = [x, y, width, height];
+        }
+        WhiteBalance::Camera => params.use_camera_wb = 1,
+        WhiteBalance::CameraOrAuto => {
+            params.use_camera_wb = 1;
+            params.use_auto_wb = 1;
+        }
+        WhiteBalance::Custom(mul) => params.user_mul = mul,
+    }
+    params.use_camera_matrix = match options.camera_matrix {
+        CameraMatrix::Never => 0,
+        CameraMatrix::Default => 1,
+        CameraMatrix::Always => 3,
+    };
+    params.output_color = options.output_color.to_libraw();
+    params.output_bps = match options.output_bps {
+        BitDepth::Eight => 8,
+        BitDepth::Sixteen => 16,
+    };
+    let (power, slope) = options.gamma.curve();
+    params.gamm[0] = 1.0 / power;
+    params.gamm[1] = slope;
+    params.bright = options.brightness;
+    params.no_auto_bright = !options.auto_brightness as i32;
+    params.auto_bright_thr = options.auto_bright_threshold;
+    params.adjust_maximum_thr = options.adjust_maximum_threshold;
+    params.threshold = options.noise_threshold;
+    params.med_passes = options.median_passes as i32;
+    params.aber[0] = 1.0 / options.chromatic_aberration[0];
+    params.aber[2] = 1.0 / options.chromatic_aberration[1];
+    params.cropbox = options.crop.unwrap_or(WHOLE_IMAGE);
+    params.user_flip = options.user_flip.map_or(-1, |flip| flip.0);
+    params.user_black = options.user_black.unwrap_or(-1);
+    params.user_cblack = options.user_cblack.map(|c| c.unwrap_or(CBLACK_UNSET));
+    params.user_sat = options.user_saturation.unwrap_or(-1);
+    params.use_fuji_rotate = options.use_fuji_rotate as i32;
+    params.green_matching = options.green_matching as i32;
+    params.dcb_iterations = options.dcb_iterations.map_or(-1, |i| i as i32);
+    params.dcb_enhance_fl = options.dcb_enhance as i32;
+    params.fbdd_noiserd = match options.fbdd_noise_reduction {
+        FbddNoiseReduction::Off => 0,
+        FbddNoiseReduction::Light => 1,
+        FbddNoiseReduction::Full => 2,
+    };
+    params.exp_correc = options.exposure.is_some() as i32;
+    if let Some(exposure) = options.exposure {
+        params.exp_shift = exposure.shift;
+        params.exp_preser = exposure.preserve_highlights;
+    }
+    params.no_auto_scale = options.no_auto_scale as i32;
+    params.no_interpolation = options.no_interpolation as i32;
+    params.output_tiff = options.output_tiff as i32;
+    params.output_profile = ptr(&strings[0]);
+    params.camera_profile = ptr(&strings[1]);
+    params.bad_pixels = ptr(&strings[2]);
+    params.dark_frame = ptr(&strings[3]);
+    Ok(strings)
+}
+
+impl Processor {
+    /// Validate `options` and write them to libraw_output_params_t
+    ///
+    /// The processor keeps the path strings alive since libraw only stores the pointers
+    pub fn set_processing_options(
+        &mut self,
+        options: &ProcessingOptions,
+    ) -> Result<(), LibrawError> {
+        // The old strings are only dropped after libraw points to the new ones
+        self.option_strings = write_options(self.params(), options)?;
+        Ok(())
+    }
+
+    /// Read the current libraw_output_params_t back as [`ProcessingOptions`]
+    pub fn processing_options(&self) -> ProcessingOptions {
+        let params = unsafe { &self.inner.as_ref().params };
+        let white_balance = if params.user_mul.iter().any(|m| *m > 0.0) {
+            WhiteBalance::Custom(params.user_mul)
+        } else {
+            match (params.use_camera_wb != 0, params.use_auto_wb != 0) {
+                (true, true) => WhiteBalance::CameraOrAuto,
+                (true, false) => WhiteBalance::Camera,
+                (false, true) if params.greybox != WHOLE_IMAGE => WhiteBalance::AutoRegion {
+                    x: params.greybox[0],
+                    y: params.greybox[1],
+                    width: params.greybox[2],
+                    height: params.greybox[3],
+                },
+                (false, true) => WhiteBalance::Auto,
+                (false, false) => WhiteBalance::Daylight,
+            }
+        };
+        let camera_profile = cstr_to_path(params.camera_profile).map(|path| {
+            if path.as_os_str() == "embed" {
+                CameraProfile::Embedded
+            } else {
+                CameraProfile::Path(path)
+            }
+        });
+        ProcessingOptions {
+            demosaic: Demosaic::from_libraw(params.user_qual),
+            half_size: params.half_size != 0,
+            four_color_rgb: params.four_color_rgb != 0,
+            highlight: HighlightMode::from_libraw(params.highlight),
+            white_balance,
+            camera_matrix: match params.use_camera_matrix {
+                0 => CameraMatrix::Never,
+                3 => CameraMatrix::Always,
+                _ => CameraMatrix::Default,
+            },
+            output_color: OutputColorSpace::from_libraw(params.output_color),
+            output_bps: match params.output_bps {
+                16 => BitDepth::Sixteen,
+                _ => BitDepth::Eight,
+            },
+            gamma: Gamma::from_libraw(&params.gamm),
+            brightness: params.bright,
+            auto_brightness: params.no_auto_bright == 0,
+            auto_bright_threshold: params.auto_bright_thr,
+            adjust_maximum_threshold: params.adjust_maximum_thr,
+            noise_threshold: params.threshold,
+            median_passes: params.med_passes.max(0) as u32,
+            chromatic_aberration: [1.0 / params.aber[0], 1.0 / params.aber[2]],
+            crop: (params.cropbox != WHOLE_IMAGE).then_some(params.cropbox),
+            user_flip: (params.user_flip >= 0).then_some(Flip(params.user_flip)),
+            user_black: (params.user_black >= 0).then_some(params.user_black),
+            user_cblack: params.user_cblack.map(|c| (c > CBLACK_UNSET).then_some(c)),
+            user_saturation: (params.user_sat != -1).then_some(params.user_sat),
+            use_fuji_rotate: params.use_fuji_rotate != 0,
+            green_matching: params.green_matching != 0,
+            dcb_iterations: u32::try_from(params.dcb_iterations).ok(),
+            dcb_enhance: params.dcb_enhance_fl != 0,
+            fbdd_noise_reduction: match params.fbdd_noiserd {
+                0 => FbddNoiseReduction::Off,
+                1 => FbddNoiseReduction::Light,
+                _ => FbddNoiseReduction::Full,
+            },
+            exposure: (params.exp_correc != 0).then_some(ExposureCorrection {
+                shift: params.exp_shift,
+                preserve_highlights: params.exp_preser,
+            }),
+            no_auto_scale: params.no_auto_scale != 0,
+            no_interpolation: params.no_interpolation != 0,
+            output_tiff: params.output_tiff != 0,
+            output_profile: cstr_to_path(params.output_profile),
+            camera_profile,
+            bad_pixels: cstr_to_path(params.bad_pixels),
+            dark_frame: cstr_to_path(params.dark_frame),
+        }
+    }
+}
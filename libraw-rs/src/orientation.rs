@@ -1 +1,533 @@
+//! Orientation of images
+//!
+//! [`Orientation`] uses the exif values (1 - 8), libraw uses [`Flip`] (`sizes.flip`, 0 - 7) which
+//! encodes the same 8 transformations as bits: 1 mirrors horizontally, 2 mirrors vertically and
+//! 4 transposes, applied in the order transpose, mirror.
 
+use crate::thumbnail::{Thumbnail, ThumbnailData};
+use crate::{ImageFormat, LibrawError, ProcessedImage};
+
+/// exif::Tag::Orientation
+/// Possible values 1,2,3,4,5,6,7,8
+///
+/// The value describes the transformation which has to be applied to the stored pixels to
+/// display the image upright
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct Orientation(pub u8);
+impl PartialEq<u8> for Orientation {
+    fn eq(&self, other: &u8) -> bool {
+        &self.0 == other
+    }
+}
+impl PartialEq<Orientation> for u8 {
+    fn eq(&self, other: &Orientation) -> bool {
+        self == &other.0
+    }
+}
+
+impl Default for Orientation {
+    fn default() -> Self {
+        Orientation::NONE
+    }
+}
+
+/// `a + b` applies `b` first and `a` to the result, see [`Orientation::then`]
+impl std::ops::Add for Orientation {
+    type Output = Self;
+    fn add(self, rhs: Orientation) -> Self::Output {
+        rhs.then(self)
+    }
+}
+impl std::ops::Neg for Orientation {
+    type Output = Self;
+    fn neg(self) -> Self::Output {
+        self.inverse()
+    }
+}
+
+impl Orientation {
+    pub const NONE: Self = Self(1);
+    pub const MIRROR_HORIZONTAL: Self = Self(2);
+    pub const CW180: Self = Self(3);
+    pub const MIRROR_VERTICAL: Self = Self(4);
+    /// Mirror along the top left to bottom right diagonal
+    pub const TRANSPOSE: Self = Self(5);
+    pub const CW90: Self = Self(6);
+    /// Mirror along the top right to bottom left diagonal
+    pub const TRANSVERSE: Self = Self(7);
+    pub const CW270: Self = Self(8);
+    pub const CCW90: Self = Self(8);
+
+    /// All 8 orientations
+    pub const ALL: [Self; 8] = [
+        Self::NONE,
+        Self::MIRROR_HORIZONTAL,
+        Self::CW180,
+        Self::MIRROR_VERTICAL,
+        Self::TRANSPOSE,
+        Self::CW90,
+        Self::TRANSVERSE,
+        Self::CW270,
+    ];
+
+    /// Whether this is one of the 8 exif values
+    pub fn is_valid(&self) -> bool {
+        (1..=8).contains(&self.0)
+    }
+
+    /// The orientation for a libraw flip, [`None`] for values libraw doesn't use
+    pub fn from_flip(flip: Flip) -> Option<Self> {
+        // Same table as dcraw uses to convert the exif tag to flip
+        const FROM_FLIP: [u8; 8] = [1, 2, 4, 3, 5, 8, 6, 7];
+        FROM_FLIP
+            .get(usize::try_from(flip.0).ok()?)
+            .map(|o| Self(*o))
+    }
+
+    /// The libraw flip for this orientation, [`None`] if the orientation is invalid
+    pub fn to_flip(&self) -> Option<Flip> {
+        const TO_FLIP: [i32; 8] = [0, 1, 3, 2, 4, 6, 7, 5];
+        TO_FLIP
+            .get((self.0 as usize).checked_sub(1)?)
+            .map(|f| Flip(*f))
+    }
+
+    /// (transpose, mirror horizontally, mirror vertically), invalid values are treated as NONE
+    fn bits(&self) -> (bool, bool, bool) {
+        let flip = self.to_flip().unwrap_or(Flip::NONE).0;
+        (flip & 4 != 0, flip & 1 != 0, flip & 2 != 0)
+    }
+
+    fn from_bits(transpose: bool, mirror_x: bool, mirror_y: bool) -> Self {
+        let flip = (transpose as i32) << 2 | (mirror_y as i32) << 1 | mirror_x as i32;
+        Self::from_flip(Flip(flip)).expect("Every 3 bit flip is valid")
+    }
+
+    /// Whether the orientation swaps width and height
+    pub fn swaps_dimensions(&self) -> bool {
+        self.bits().0
+    }
+
+    /// Whether the orientation mirrors the image (2, 4, 5 and 7)
+    pub fn is_mirrored(&self) -> bool {
+        matches!(self.0, 2 | 4 | 5 | 7)
+    }
+
+    /// The orientation which applies `self` first and `next` to the result
+    pub fn then(self, next: Orientation) -> Self {
+        let (t1, x1, y1) = self.bits();
+        let (t2, x2, y2) = next.bits();
+        // Moving the mirror of `next` in front of the transpose of `self` swaps its axes
+        let (x2, y2) = if t1 { (y2, x2) } else { (x2, y2) };
+        Self::from_bits(t1 ^ t2, x1 ^ x2, y1 ^ y2)
+    }
+
+    /// The orientation which undoes `self`
+    pub fn inverse(self) -> Self {
+        let (t, x, y) = self.bits();
+        if t {
+            Self::from_bits(t, y, x)
+        } else {
+            self
+        }
+    }
+
+    /// Width and height of an image of `width` x `height` after applying the orientation
+    pub fn oriented_size(&self, width: u32, height: u32) -> (u32, u32) {
+        if self.swaps_dimensions() {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
+
+    /// Apply the orientation to interleaved pixels with `channels` samples per pixel
+    ///
+    /// Returns the transformed pixels, the size of the result is [`Orientation::oriented_size`]
+    pub fn apply<T: Copy>(
+        &self,
+        data: &[T],
+        width: usize,
+        height: usize,
+        channels: usize,
+    ) -> Result<Vec<T>, LibrawError> {
+        if data.len() < width * height * channels {
+            return Err(LibrawError::InvalidImageLayout(
+                "data is shorter than the image",
+            ));
+        }
+        let (transpose, mirror_x, mirror_y) = self.bits();
+        let (out_width, out_height) = if transpose {
+            (height, width)
+        } else {
+            (width, height)
+        };
+        let mut out = Vec::with_capacity(width * height * channels);
+        for oy in 0..out_height {
+            for ox in 0..out_width {
+                let (x, y) = if transpose { (oy, ox) } else { (ox, oy) };
+                let x = if mirror_x { width - 1 - x } else { x };
+                let y = if mirror_y { height - 1 - y } else { y };
+                let offset = (y * width + x) * channels;
+                out.extend_from_slice(&data[offset..offset + channels]);
+            }
+        }
+        Ok(out)
+    }
+
+    /// Set the orientation of a jpeg
+    ///
+    /// Only the orientation tag of the EXIF data is updated (or inserted), every other tag and the
+    /// XMP packet are kept. A `tiff:Orientation` in the XMP packet is updated as well so viewers
+    /// which prefer XMP don't rotate the image twice.
+    #[cfg(feature = "jpeg")]
+    pub fn add_to(self, buffer: Vec<u8>) -> Result<Vec<u8>, LibrawError> {
+        self.write_to_jpeg(buffer, false)
+    }
+
+    /// Set the orientation of a jpeg and strip every other EXIF tag and the XMP packet
+    #[cfg(feature = "jpeg")]
+    pub fn add_to_stripped(self, buffer: Vec<u8>) -> Result<Vec<u8>, LibrawError> {
+        self.write_to_jpeg(buffer, true)
+    }
+
+    #[cfg(feature = "jpeg")]
+    fn write_to_jpeg(self, mut buffer: Vec<u8>, strip: bool) -> Result<Vec<u8>, LibrawError> {
+        use img_parts::ImageEXIF;
+        if !self.is_valid() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "Orientation must be between 1 and 8",
+            )
+            .into());
+        }
+
+        let mut jpeg =
+            img_parts::jpeg::Jpeg::from_bytes(img_parts::Bytes::from_iter(buffer.drain(..)))?;
+        let exif = if strip {
+            Orientation::__remove_xmp(&mut jpeg);
+            Self::exif_data_with_orientation(self.0)
+        } else {
+            Orientation::__update_xmp(&mut jpeg, self.0);
+            // Fall back to an orientation only EXIF if the existing one can't be parsed
+            jpeg.exif()
+                .and_then(|exif| set_exif_orientation(&exif, self.0))
+                .unwrap_or_else(|| Self::exif_data_with_orientation(self.0))
+        };
+        set_exif_segment(&mut jpeg, &exif);
+        jpeg.encoder().write_to(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    #[cfg(feature = "jpeg")]
+    fn __update_xmp(jpeg: &mut img_parts::jpeg::Jpeg, o: u8) {
+        for segment in jpeg.segments_mut() {
+            if segment.marker() == 0xe1 && segment.contents().starts_with(b"http://ns.adobe.com/") {
+                let mut contents = segment.contents().to_vec();
+                if set_xmp_orientation(&mut contents, o) {
+                    *segment =
+                        img_parts::jpeg::JpegSegment::new_with_contents(0xe1, contents.into());
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "jpeg")]
+    fn __remove_xmp(jpeg: &mut img_parts::jpeg::Jpeg) {
+        jpeg.segments_mut().retain(|segment| {
+            !(segment.marker() == 0xe1 && segment.contents().starts_with(b"http://ns.adobe.com/"))
+        });
+    }
+
+    /// This encodes the orientation into a raw exif container data
+    #[cfg(feature = "jpeg")]
+    fn exif_data_with_orientation(o: u8) -> Vec<u8> {
+        vec![
+            0x4d, 0x4d, 0x0, 0x2a, 0x0, 0x0, 0x0, 0x8, 0x0, 0x1, 0x1, 0x12, 0x0, 0x3, 0x0, 0x0,
+            0x0, 0x1, 0x0, o, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+        ]
+    }
+}
+
+/// libraw_data_t.sizes.flip
+/// Possible values 0 - 7, see the module documentation
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Flip(pub i32);
+impl Flip {
+    pub const NONE: Self = Self(0);
+    pub const MIRROR_HORIZONTAL: Self = Self(1);
+    pub const MIRROR_VERTICAL: Self = Self(2);
+    pub const CW180: Self = Self(3);
+    pub const TRANSPOSE: Self = Self(4);
+    pub const CW90: Self = Self(6);
+    pub const TRANSVERSE: Self = Self(7);
+    pub const CW270: Self = Self(5);
+    pub const CCW90: Self = Self(5);
+}
+
+impl Default for Flip {
+    fn default() -> Self {
+        Flip::NONE
+    }
+}
+
+impl From<i32> for Flip {
+    fn from(flip: i32) -> Self {
+        Self(flip)
+    }
+}
+
+/// Flip values libraw doesn't use map to [`Orientation::NONE`], use [`Orientation::from_flip`] to
+/// detect them
+impl From<Flip> for Orientation {
+    fn from(flip: Flip) -> Self {
+        Orientation::from_flip(flip).unwrap_or(Orientation::NONE)
+    }
+}
+
+/// Invalid orientations map to [`Flip::NONE`]
+impl From<Orientation> for Flip {
+    fn from(orientation: Orientation) -> Self {
+        orientation.to_flip().unwrap_or(Flip::NONE)
+    }
+}
+
+impl ProcessedImage {
+    /// Rotate / mirror the pixels in place
+    ///
+    /// libraw already applies `sizes.flip` in make_mem_image (unless `params.user_flip` is 0), this
+    /// is for outputs which can't carry the orientation as metadata.
+    pub fn apply_orientation(&mut self, orientation: Orientation) -> Result<(), LibrawError> {
+        if !matches!(self.type_(), ImageFormat::Bitmap) {
+            return Err(LibrawError::InvalidImageLayout("the image is not a bitmap"));
+        }
+        let raw = self.raw();
+        let (width, height) = (raw.width as usize, raw.height as usize);
+        let channels = raw.colors as usize;
+        match raw.bits {
+            8 => {
+                let pixels =
+                    orientation.apply(self.samples::<u8>(8, None)?, width, height, channels)?;
+                self.as_mut_slice::<u8>()[..pixels.len()].copy_from_slice(&pixels);
+            }
+            16 => {
+                let pixels =
+                    orientation.apply(self.samples::<u16>(16, None)?, width, height, channels)?;
+                self.as_mut_slice::<u16>()[..pixels.len()].copy_from_slice(&pixels);
+            }
+            bits => return Err(LibrawError::InvalidColor(bits)),
+        }
+        let (width, height) = orientation.oriented_size(width as u32, height as u32);
+        let raw = unsafe { self.inner.as_mut() };
+        raw.width = width as u16;
+        raw.height = height as u16;
+        Ok(())
+    }
+
+    fn as_mut_slice<T>(&mut self) -> &mut [T] {
+        unsafe {
+            std::slice::from_raw_parts_mut(
+                self.inner.as_mut().data.as_mut_ptr() as *mut T,
+                self.inner.as_ref().data_size as usize / std::mem::size_of::<T>(),
+            )
+        }
+    }
+}
+
+impl Thumbnail {
+    /// Rotate / mirror the pixels of a bitmap thumbnail
+    ///
+    /// Jpeg and heif previews can't be transformed without decoding them, tag them with
+    /// [`Orientation::add_to`] instead
+    pub fn apply_orientation(&mut self, orientation: Orientation) -> Result<(), LibrawError> {
+        let (width, height) = (self.width as usize, self.height as usize);
+        match &mut self.data {
+            ThumbnailData::Rgb8(data) => *data = orientation.apply(data, width, height, 3)?,
+            ThumbnailData::Rgb16(data) => *data = orientation.apply(data, width, height, 3)?,
+            ThumbnailData::Jpeg(_) | ThumbnailData::Heif(_) => {
+                return Err(LibrawError::UnsupportedThumbnail)
+            }
+        }
+        (self.width, self.height) = orientation.oriented_size(self.width, self.height);
+        Ok(())
+    }
+}
+
+/// Put `exif` (a tiff structure) into the EXIF segment of a jpeg
+///
+/// An existing EXIF segment is replaced unless `keep_existing` is set
+#[cfg(feature = "jpeg")]
+pub(crate) fn set_jpeg_exif(
+    mut buffer: Vec<u8>,
+    exif: &[u8],
+    keep_existing: bool,
+) -> Result<Vec<u8>, LibrawError> {
+    use img_parts::ImageEXIF;
+    let mut jpeg =
+        img_parts::jpeg::Jpeg::from_bytes(img_parts::Bytes::from_iter(buffer.drain(..)))?;
+    if !(keep_existing && jpeg.exif().is_some()) {
+        set_exif_segment(&mut jpeg, exif);
+    }
+    jpeg.encoder().write_to(&mut buffer)?;
+    Ok(buffer)
+}
+
+#[cfg(feature = "jpeg")]
+fn set_exif_segment(jpeg: &mut img_parts::jpeg::Jpeg, exif: &[u8]) {
+    let mut contents = b"Exif\0\0".to_vec();
+    contents.extend_from_slice(exif);
+    let segment = img_parts::jpeg::JpegSegment::new_with_contents(0xe1, contents.into());
+    let segments = jpeg.segments_mut();
+    // Replace the segment in place to keep the order of the segments, otherwise put it right
+    // after JFIF (Jpeg::set_exif panics on jpegs with less than 3 segments)
+    match segments
+        .iter()
+        .position(|segment| segment.marker() == 0xe1 && segment.contents().starts_with(b"Exif\0\0"))
+    {
+        Some(index) => segments[index] = segment,
+        None => {
+            let index = segments
+                .iter()
+                .position(|segment| segment.marker() != 0xe0)
+                .unwrap_or(segments.len());
+            segments.insert(index, segment);
+        }
+    }
+}
+
+/// Size limit of the tiff structure in a jpeg APP1 segment (65535 - length - "Exif\0\0")
+#[cfg(feature = "jpeg")]
+const MAX_APP1_EXIF: usize = 65535 - 2 - 6;
+
+/// Set the orientation tag (0x0112) in IFD0 of an EXIF tiff structure
+///
+/// Every other entry is kept as-is. When the tag is missing IFD0 is copied to the end of the
+/// structure with the new entry so none of the existing offsets change. Returns [`None`] if the
+/// structure can't be parsed or would no longer fit in a jpeg segment.
+#[cfg(feature = "jpeg")]
+pub(crate) fn set_exif_orientation(tiff: &[u8], orientation: u8) -> Option<Vec<u8>> {
+    let big_endian = match tiff.get(..2)? {
+        b"MM" => true,
+        b"II" => false,
+        _ => return None,
+    };
+    let read_u16 = |offset: usize| -> Option<u16> {
+        let b = tiff.get(offset..offset + 2)?;
+        Some(if big_endian {
+            u16::from_be_bytes([b[0], b[1]])
+        } else {
+            u16::from_le_bytes([b[0], b[1]])
+        })
+    };
+    let read_u32 = |offset: usize| -> Option<u32> {
+        let b = tiff.get(offset..offset + 4)?;
+        Some(if big_endian {
+            u32::from_be_bytes([b[0], b[1], b[2], b[3]])
+        } else {
+            u32::from_le_bytes([b[0], b[1], b[2], b[3]])
+        })
+    };
+    let short = |v: u16| {
+        if big_endian {
+            v.to_be_bytes()
+        } else {
+            v.to_le_bytes()
+        }
+    };
+    let long = |v: u32| {
+        if big_endian {
+            v.to_be_bytes()
+        } else {
+            v.to_le_bytes()
+        }
+    };
+
+    if read_u16(2)? != 42 {
+        return None;
+    }
+    let ifd = read_u32(4)? as usize;
+    let count = read_u16(ifd)? as usize;
+    let entries = ifd + 2;
+    let next = entries + count * 12;
+    // The entries and the offset of the next IFD have to be in bounds
+    tiff.get(entries..next + 4)?;
+
+    // tag, SHORT, count 1, value padded to 4 bytes
+    let mut entry = Vec::with_capacity(12);
+    entry.extend_from_slice(&short(0x0112));
+    entry.extend_from_slice(&short(3));
+    entry.extend_from_slice(&long(1));
+    entry.extend_from_slice(&short(orientation as u16));
+    entry.extend_from_slice(&[0, 0]);
+
+    let mut out = tiff.to_vec();
+    for i in 0..count {
+        let offset = entries + i * 12;
+        if read_u16(offset)? == 0x0112 {
+            out[offset..offset + 12].copy_from_slice(&entry);
+            return Some(out);
+        }
+    }
+
+    // IFD offsets have to be word aligned
+    if out.len() % 2 == 1 {
+        out.push(0);
+    }
+    let moved = out.len();
+    out.extend_from_slice(&short(count as u16 + 1));
+    let mut inserted = false;
+    for i in 0..count {
+        let offset = entries + i * 12;
+        // Entries are sorted by tag
+        if !inserted && read_u16(offset)? > 0x0112 {
+            out.extend_from_slice(&entry);
+            inserted = true;
+        }
+        out.extend_from_slice(&tiff[offset..offset + 12]);
+    }
+    if !inserted {
+        out.extend_from_slice(&entry);
+    }
+    out.extend_from_slice(&tiff[next..next + 4]);
+    out[4..8].copy_from_slice(&long(moved as u32));
+
+    (out.len() <= MAX_APP1_EXIF).then_some(out)
+}
+
+/// Rewrite `tiff:Orientation` in an XMP packet in place
+///
+/// Handles both the attribute and the element form, returns whether anything was changed
+#[cfg(feature = "jpeg")]
+pub(crate) fn set_xmp_orientation(xmp: &mut [u8], orientation: u8) -> bool {
+    if !(1..=8).contains(&orientation) {
+        return false;
+    }
+    let mut changed = false;
+    for pattern in [
+        &b"tiff:Orientation=\""[..],
+        b"tiff:Orientation='",
+        b"<tiff:Orientation>",
+    ] {
+        let mut start = 0;
+        while let Some(pos) = find(&xmp[start..], pattern) {
+            let value = start + pos + pattern.len();
+            // Only replace single digit values so the length of the packet doesn't change
+            if xmp.get(value).map_or(false, u8::is_ascii_digit)
+                && !xmp.get(value + 1).map_or(false, u8::is_ascii_digit)
+            {
+                xmp[value] = b'0' + orientation;
+                changed = true;
+            }
+            start = value;
+        }
+    }
+    changed
+}
+
+#[cfg(feature = "jpeg")]
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
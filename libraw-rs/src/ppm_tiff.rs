@@ -0,0 +1,83 @@
+//! Rust version of libraw's dcraw_ppm_tiff_writer which writes to any [`Write`]
+//!
+//! Like libraw it writes a PGM (1 color), PPM (3 colors) or PAM (any other number of colors),
+//! or a tiff when `params.output_tiff` is set, with `params.output_bps` bits per sample in big
+//! endian.
+
+use std::io::Write;
+
+use crate::tiff::{Ifd, Samples, Value, INTER_COLOR_PROFILE};
+use crate::traits::LRString;
+use crate::{LibrawError, Orientation, ProcessedImage, Processor};
+
+/// Write a binary PGM / PPM / PAM
+fn write_pnm(
+    out: &mut impl Write,
+    width: u32,
+    height: u32,
+    colors: u16,
+    samples: Samples<'_>,
+    tuple_type: &str,
+) -> std::io::Result<()> {
+    let max = match samples {
+        Samples::U8(_) => u8::MAX as u32,
+        Samples::U16(_) => u16::MAX as u32,
+    };
+    match colors {
+        1 | 3 => write!(out, "P{}\n{} {}\n{}\n", colors / 2 + 5, width, height, max)?,
+        _ => write!(
+            out,
+            "P7\nWIDTH {}\nHEIGHT {}\nDEPTH {}\nMAXVAL {}\nTUPLTYPE {}\nENDHDR\n",
+            width, height, colors, max, tuple_type
+        )?,
+    }
+    samples.write_be(out)
+}
+
+impl Processor {
+    /// Write the processed image as a ppm or tiff (depending on `params().output_tiff`) into
+    /// `writer`
+    ///
+    /// Call `dcraw_process` first, the processor can be used afterwards.
+    pub fn write_ppm_tiff<W: Write>(&mut self, writer: W) -> Result<(), LibrawError> {
+        let image = self.dcraw_process_make_mem_image()?;
+        self.write_ppm_tiff_image(&image, writer)
+    }
+
+    /// Write an already processed image (eg. from [`Processor::render`]) as a ppm or tiff
+    /// (depending on `params().output_tiff`)
+    ///
+    /// The tiff gets the EXIF from [`Processor::exif_builder`] if [`Processor::embed_exif`] is
+    /// set and [`Processor::icc_profile`].
+    pub fn write_ppm_tiff_image<W: Write>(
+        &self,
+        image: &ProcessedImage,
+        mut writer: W,
+    ) -> Result<(), LibrawError> {
+        let (width, height, colors) = (image.width(), image.height(), image.colors());
+        let samples = match image.bits() {
+            8 => Samples::U8(image.samples(8, None)?),
+            16 => Samples::U16(image.samples(16, None)?),
+            bits => return Err(LibrawError::InvalidColor(bits)),
+        };
+        let output_tiff = unsafe { self.inner.as_ref().params.output_tiff } != 0;
+        if output_tiff {
+            let (mut ifd0, sub_ifds) = if self.embed_exif {
+                // make_mem_image already applied the flip
+                self.exif_builder().orientation(Orientation::NONE).ifds()
+            } else {
+                (Ifd::new(), Vec::new())
+            };
+            if let Some(icc) = self.icc_profile_for(&self.icc_profile, colors)? {
+                ifd0.insert(INTER_COLOR_PROFILE, Value::Undefined(icc));
+            }
+            crate::tiff::write_image(&mut writer, width, height, colors, samples, ifd0, &sub_ifds)?;
+        } else {
+            let cdesc = self.idata().cdesc.as_ascii();
+            let tuple_type = cdesc.get(..colors as usize).unwrap_or(cdesc);
+            write_pnm(&mut writer, width, height, colors, samples, tuple_type)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,261 @@
+//! Named [`ProcessingOptions`] presets and rules to pick one for the opened file
+//!
+//! With the `toml` / `json` features a [`PresetLibrary`] can be loaded from a file so the
+//! rendering can be tweaked without a rebuild, eg.
+//!
+//! ```toml
+//! default = "neutral"
+//!
+//! [[preset]]
+//! name = "neutral"
+//!
+//! [[preset]]
+//! name = "xtrans"
+//! description = "AHD, which is 3 pass Markesteijn on X-Trans"
+//! [preset.options]
+//! demosaic = "ahd"
+//! white_balance = "camera"
+//!
+//! [[rule]]
+//! make = "fujifilm"
+//! xtrans = true
+//! preset = "xtrans"
+//! ```
+
+use std::collections::HashSet;
+
+use crate::metadata::Metadata;
+use crate::{LibrawError, ProcessingOptions, Processor};
+
+/// A named set of [`ProcessingOptions`]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProcessingPreset {
+    pub name: String,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub description: Option<String>,
+    /// Options missing in the file keep libraw's defaults
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub options: ProcessingOptions,
+}
+
+impl ProcessingPreset {
+    pub fn new(name: impl Into<String>, options: ProcessingOptions) -> Self {
+        Self {
+            name: name.into(),
+            description: None,
+            options,
+        }
+    }
+}
+
+/// Picks a preset for the files matching all of the given fields
+///
+/// `make`, `model` and `lens` are case insensitive patterns where `*` matches any run of
+/// characters, `make` and `model` are matched against both the raw and the normalized names.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PresetRule {
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub make: Option<String>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub model: Option<String>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub lens: Option<String>,
+    /// Only match X-Trans (`true`) or only other (`false`) sensors
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub xtrans: Option<bool>,
+    /// Name of the preset to use
+    pub preset: String,
+}
+
+impl PresetRule {
+    pub fn matches(&self, metadata: &Metadata) -> bool {
+        let camera = &metadata.camera;
+        let either = |pattern: &Option<String>, a: &str, b: &str| {
+            pattern
+                .as_deref()
+                .map_or(true, |p| pattern_matches(p, a) || pattern_matches(p, b))
+        };
+        either(&self.make, &camera.make, &camera.normalized_make)
+            && either(&self.model, &camera.model, &camera.normalized_model)
+            && self.lens.as_deref().map_or(true, |p| {
+                metadata
+                    .lens
+                    .model
+                    .as_deref()
+                    .map_or(false, |lens| pattern_matches(p, lens))
+            })
+            // libraw marks X-Trans sensors with filters = 9
+            && self.xtrans.map_or(true, |x| x == (camera.filters == 9))
+    }
+}
+
+/// Case insensitive match where `*` matches any run of characters
+fn pattern_matches(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.to_lowercase().chars().collect::<Vec<_>>();
+    let text = text.to_lowercase().chars().collect::<Vec<_>>();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last star and the text position it was tried at
+    let mut star = None;
+    while t < text.len() {
+        if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == text[t] {
+            p += 1;
+            t += 1;
+        } else if let Some((sp, st)) = star {
+            p = sp + 1;
+            t = st + 1;
+            star = Some((sp, st + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// A set of presets with the rules to pick one of them
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PresetLibrary {
+    /// Used when no rule matches
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub default: Option<String>,
+    #[cfg_attr(feature = "serde", serde(default, rename = "preset"))]
+    pub presets: Vec<ProcessingPreset>,
+    /// Tried in order, the first match wins
+    #[cfg_attr(feature = "serde", serde(default, rename = "rule"))]
+    pub rules: Vec<PresetRule>,
+}
+
+impl PresetLibrary {
+    /// The presets of [`crate::defaults`]
+    pub fn builtin() -> Self {
+        Self {
+            default: None,
+            presets: crate::defaults::presets(),
+            rules: Vec::new(),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ProcessingPreset> {
+        self.presets.iter().find(|preset| preset.name == name)
+    }
+
+    /// The preset of the first rule matching `metadata`, or the default one
+    pub fn select(&self, metadata: &Metadata) -> Option<&ProcessingPreset> {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(metadata))
+            .map(|rule| rule.preset.as_str())
+            .or(self.default.as_deref())
+            .and_then(|name| self.get(name))
+    }
+
+    /// Check that the names are unique, every referenced preset exists and the options are valid
+    pub fn validate(&self) -> Result<(), LibrawError> {
+        let mut names = HashSet::new();
+        for preset in &self.presets {
+            if !names.insert(preset.name.as_str()) {
+                return Err(LibrawError::InvalidPreset(format!(
+                    "duplicate preset {:?}",
+                    preset.name
+                )));
+            }
+            preset.options.validate().map_err(|e| {
+                LibrawError::InvalidPreset(format!("preset {:?}: {}", preset.name, e))
+            })?;
+        }
+        let references = self
+            .rules
+            .iter()
+            .map(|rule| &rule.preset)
+            .chain(self.default.as_ref());
+        for name in references {
+            if !names.contains(name.as_str()) {
+                return Err(LibrawError::InvalidPreset(format!(
+                    "unknown preset {:?}",
+                    name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Parse and validate a library in TOML
+    #[cfg(feature = "toml")]
+    pub fn from_toml_str(toml: &str) -> Result<Self, LibrawError> {
+        let library: Self = toml::from_str(toml)?;
+        library.validate()?;
+        Ok(library)
+    }
+
+    #[cfg(feature = "toml")]
+    pub fn to_toml_string(&self) -> Result<String, LibrawError> {
+        Ok(toml::to_string_pretty(self)?)
+    }
+
+    /// Parse and validate a library in JSON
+    #[cfg(feature = "json")]
+    pub fn from_json_str(json: &str) -> Result<Self, LibrawError> {
+        let library: Self = serde_json::from_str(json)?;
+        library.validate()?;
+        Ok(library)
+    }
+
+    #[cfg(feature = "json")]
+    pub fn to_json_string(&self) -> Result<String, LibrawError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Load a `.toml` or `.json` library
+    #[cfg(any(feature = "toml", feature = "json"))]
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<Self, LibrawError> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+        match path.extension().and_then(|e| e.to_str()) {
+            #[cfg(feature = "toml")]
+            Some(e) if e.eq_ignore_ascii_case("toml") => Self::from_toml_str(&contents),
+            #[cfg(feature = "json")]
+            Some(e) if e.eq_ignore_ascii_case("json") => Self::from_json_str(&contents),
+            _ => Err(LibrawError::InvalidPreset(format!(
+                "unsupported preset file {}",
+                path.display()
+            ))),
+        }
+    }
+}
+
+impl Processor {
+    /// Apply the preset `library` picks for the opened file
+    pub fn apply_preset<'a>(
+        &mut self,
+        library: &'a PresetLibrary,
+    ) -> Result<Option<&'a ProcessingPreset>, LibrawError> {
+        let preset = library.select(&self.metadata());
+        if let Some(preset) = preset {
+            self.set_processing_options(&preset.options)?;
+        }
+        Ok(preset)
+    }
+}
@@ -0,0 +1,155 @@
+//! Checked access to the pixels of a [`ProcessedImage`] and conversions into owned buffers
+//!
+//! libraw lays the bitmap out as packed rows of `colors` samples of `bits` each, 16 bit samples
+//! are in native endian.
+
+use crate::{ImageFormat, LibrawError, ProcessedImage};
+
+impl ProcessedImage {
+    /// The `colors * width * height` samples of a bitmap with `bits` bits per sample, as `T`s which
+    /// are made of whole samples (eg. `u8` or `[u8; 3]` for 8 bits)
+    pub(crate) fn samples<T>(&self, bits: u16, colors: Option<u16>) -> Result<&[T], LibrawError> {
+        if !matches!(self.type_(), ImageFormat::Bitmap) {
+            return Err(LibrawError::InvalidImageLayout("the image is not a bitmap"));
+        }
+        if self.bits() != bits {
+            return Err(LibrawError::InvalidColor(self.bits()));
+        }
+        if colors.map_or(false, |colors| colors != self.colors()) {
+            return Err(LibrawError::InvalidImageLayout(
+                "unexpected number of colors",
+            ));
+        }
+        let bytes = self.width() as usize
+            * self.height() as usize
+            * self.colors() as usize
+            * (bits as usize / 8);
+        let len = bytes / std::mem::size_of::<T>();
+        if self.size() < bytes {
+            return Err(LibrawError::InvalidImageLayout(
+                "data is shorter than the image",
+            ));
+        }
+        let data = self.raw().data.as_ptr();
+        if data as usize % std::mem::align_of::<T>() != 0 {
+            return Err(LibrawError::InvalidImageLayout("data is not aligned"));
+        }
+        Ok(unsafe { std::slice::from_raw_parts(data as *const T, len) })
+    }
+
+    /// Pixels of an 8 bit rgb bitmap
+    pub fn rgb8(&self) -> Result<&[[u8; 3]], LibrawError> {
+        self.samples(8, Some(3))
+    }
+
+    /// Pixels of a 16 bit rgb bitmap
+    pub fn rgb16(&self) -> Result<&[[u16; 3]], LibrawError> {
+        self.samples(16, Some(3))
+    }
+
+    /// Pixels of an 8 bit single color bitmap
+    pub fn gray8(&self) -> Result<&[u8], LibrawError> {
+        self.samples(8, Some(1))
+    }
+
+    /// Pixels of a 16 bit single color bitmap
+    pub fn gray16(&self) -> Result<&[u16], LibrawError> {
+        self.samples(16, Some(1))
+    }
+
+    /// The samples of an 8 bit bitmap or the bytes of a jpeg
+    pub fn into_vec_u8(self) -> Result<Vec<u8>, LibrawError> {
+        match self.type_() {
+            ImageFormat::Jpeg => Ok(self.as_slice_u8().to_vec()),
+            ImageFormat::Bitmap => Ok(self.samples::<u8>(8, None)?.to_vec()),
+        }
+    }
+
+    /// The samples of a 16 bit bitmap
+    pub fn into_vec_u16(self) -> Result<Vec<u16>, LibrawError> {
+        Ok(self.samples::<u16>(16, None)?.to_vec())
+    }
+}
+
+#[cfg(feature = "image")]
+mod image_conversions {
+    use image::{DynamicImage, GrayImage, ImageBuffer, Luma, Rgb, RgbImage};
+
+    use super::*;
+
+    fn buffer<P: image::Pixel>(
+        image: &ProcessedImage,
+        samples: Vec<P::Subpixel>,
+    ) -> Result<ImageBuffer<P, Vec<P::Subpixel>>, LibrawError> {
+        ImageBuffer::from_raw(image.width(), image.height(), samples)
+            .ok_or(LibrawError::EncodingError)
+    }
+
+    impl TryFrom<ProcessedImage> for RgbImage {
+        type Error = LibrawError;
+        fn try_from(image: ProcessedImage) -> Result<Self, Self::Error> {
+            let samples = image.samples::<u8>(8, Some(3))?.to_vec();
+            buffer(&image, samples)
+        }
+    }
+
+    impl TryFrom<ProcessedImage> for ImageBuffer<Rgb<u16>, Vec<u16>> {
+        type Error = LibrawError;
+        fn try_from(image: ProcessedImage) -> Result<Self, Self::Error> {
+            let samples = image.samples::<u16>(16, Some(3))?.to_vec();
+            buffer(&image, samples)
+        }
+    }
+
+    impl TryFrom<ProcessedImage> for GrayImage {
+        type Error = LibrawError;
+        fn try_from(image: ProcessedImage) -> Result<Self, Self::Error> {
+            let samples = image.samples::<u8>(8, Some(1))?.to_vec();
+            buffer(&image, samples)
+        }
+    }
+
+    impl TryFrom<ProcessedImage> for ImageBuffer<Luma<u16>, Vec<u16>> {
+        type Error = LibrawError;
+        fn try_from(image: ProcessedImage) -> Result<Self, Self::Error> {
+            let samples = image.samples::<u16>(16, Some(1))?.to_vec();
+            buffer(&image, samples)
+        }
+    }
+
+    impl ProcessedImage {
+        /// Copy into a [`DynamicImage`], jpegs are decoded, bitmaps need 1 or 3 colors
+        pub fn to_dynamic_image(&self) -> Result<DynamicImage, LibrawError> {
+            if matches!(self.type_(), ImageFormat::Jpeg) {
+                return Ok(image::load_from_memory_with_format(
+                    self.as_slice_u8(),
+                    image::ImageFormat::Jpeg,
+                )?);
+            }
+            Ok(match (self.colors(), self.bits()) {
+                (3, 8) => DynamicImage::ImageRgb8(buffer(self, self.samples(8, None)?.to_vec())?),
+                (3, 16) => {
+                    DynamicImage::ImageRgb16(buffer(self, self.samples(16, None)?.to_vec())?)
+                }
+                (1, 8) => DynamicImage::ImageLuma8(buffer(self, self.samples(8, None)?.to_vec())?),
+                (1, 16) => {
+                    DynamicImage::ImageLuma16(buffer(self, self.samples(16, None)?.to_vec())?)
+                }
+                (1 | 3, bits) => return Err(LibrawError::InvalidColor(bits)),
+                _ => {
+                    return Err(LibrawError::InvalidImageLayout(
+                        "unexpected number of colors",
+                    ))
+                }
+            })
+        }
+    }
+
+    /// See [`ProcessedImage::to_dynamic_image`]
+    impl TryFrom<ProcessedImage> for DynamicImage {
+        type Error = LibrawError;
+        fn try_from(image: ProcessedImage) -> Result<Self, Self::Error> {
+            image.to_dynamic_image()
+        }
+    }
+}
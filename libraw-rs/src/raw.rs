@@ -0,0 +1,386 @@
+//! Access to the unpacked raw sensor data
+//!
+//! After `unpack` libraw keeps the undemosaiced data in `rawdata.raw_image` with the layout
+//! described by `rawdata.sizes`. [`RawImage`] is a bounds checked view over it which borrows the
+//! [`Processor`], so it can't outlive an `unpack` / `recycle` / drop.
+
+use crate::traits::LRString;
+use crate::{LibrawError, Processor};
+
+/// The 2x2 arrangement of a bayer color filter array
+///
+/// The name reads the colors of the top left 2x2 block row by row
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CfaPattern {
+    Rggb,
+    Bggr,
+    Grbg,
+    Gbrg,
+}
+
+impl CfaPattern {
+    /// Build the pattern from the colors of the top left 2x2 block
+    pub fn from_colors(colors: [[char; 2]; 2]) -> Option<Self> {
+        use CfaPattern::*;
+        match colors {
+            [['R', 'G'], ['G', 'B']] => Some(Rggb),
+            [['B', 'G'], ['G', 'R']] => Some(Bggr),
+            [['G', 'R'], ['B', 'G']] => Some(Grbg),
+            [['G', 'B'], ['R', 'G']] => Some(Gbrg),
+            _ => None,
+        }
+    }
+
+    /// The colors of the top left 2x2 block
+    pub fn colors(&self) -> [[char; 2]; 2] {
+        use CfaPattern::*;
+        match self {
+            Rggb => [['R', 'G'], ['G', 'B']],
+            Bggr => [['B', 'G'], ['G', 'R']],
+            Grbg => [['G', 'R'], ['B', 'G']],
+            Gbrg => [['G', 'B'], ['R', 'G']],
+        }
+    }
+
+    /// The color of the pixel at (row, col)
+    pub fn color_at(&self, row: usize, col: usize) -> char {
+        self.colors()[row & 1][col & 1]
+    }
+}
+
+/// A borrowed view over the unpacked raw data
+///
+/// `T` is the type of a single pixel, `u16` for bayer data and `[u16; 3]`, `f32`, ... for the
+/// other layouts in [`RawData`].
+///
+/// Coordinates passed to the methods without a `visible_` prefix are relative to the full sensor
+/// area (including the margins), the `visible_` ones are relative to the top left visible pixel.
+pub struct RawImage<'p, T = u16> {
+    processor: &'p Processor,
+    data: &'p [T],
+    /// Number of pixels per row (can be larger than raw_width)
+    pitch: usize,
+    raw_width: usize,
+    raw_height: usize,
+    width: usize,
+    height: usize,
+    top_margin: usize,
+    left_margin: usize,
+}
+
+impl<'p, T> Clone for RawImage<'p, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<'p, T> Copy for RawImage<'p, T> {}
+
+impl<'p, T> std::fmt::Debug for RawImage<'p, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RawImage")
+            .field("raw_width", &self.raw_width)
+            .field("raw_height", &self.raw_height)
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("top_margin", &self.top_margin)
+            .field("left_margin", &self.left_margin)
+            .finish()
+    }
+}
+
+/// The raw data in whichever layout the decoder produced
+///
+/// Most cameras produce [`RawData::Bayer`]. Linear DNGs and sRAW files are unpacked into the
+/// 3 / 4 channel variants and floating point DNGs into the float ones.
+#[derive(Debug, Clone, Copy)]
+pub enum RawData<'p> {
+    /// rawdata.raw_image, single channel color filter array data
+    Bayer(RawImage<'p, u16>),
+    /// rawdata.color3_image
+    Color3(RawImage<'p, [u16; 3]>),
+    /// rawdata.color4_image
+    Color4(RawImage<'p, [u16; 4]>),
+    /// rawdata.float_image
+    Float(RawImage<'p, f32>),
+    /// rawdata.float3_image
+    Float3(RawImage<'p, [f32; 3]>),
+    /// rawdata.float4_image
+    Float4(RawImage<'p, [f32; 4]>),
+}
+
+impl<'p> RawData<'p> {
+    /// Number of channels per pixel
+    pub fn channels(&self) -> usize {
+        match self {
+            RawData::Bayer(_) | RawData::Float(_) => 1,
+            RawData::Color3(_) | RawData::Float3(_) => 3,
+            RawData::Color4(_) | RawData::Float4(_) => 4,
+        }
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(
+            self,
+            RawData::Float(_) | RawData::Float3(_) | RawData::Float4(_)
+        )
+    }
+}
+
+impl Processor {
+    /// Get a view over the bayer data in rawdata.raw_image
+    ///
+    /// Returns [`LibrawError::RawDataMissing`] if `unpack` hasn't been called or if the decoder
+    /// didn't produce single channel bayer data, use [`Processor::raw_data`] for the other layouts
+    pub fn raw_image(&self) -> Result<RawImage<'_>, LibrawError> {
+        let rawdata = unsafe { &self.inner.as_ref().rawdata };
+        unsafe { RawImage::new(self, rawdata.raw_image) }.ok_or(LibrawError::RawDataMissing)
+    }
+
+    /// Get a view over the raw data in whichever layout the decoder produced
+    pub fn raw_data(&self) -> Result<RawData<'_>, LibrawError> {
+        let rawdata = unsafe { &self.inner.as_ref().rawdata };
+        unsafe {
+            RawImage::new(self, rawdata.raw_image)
+                .map(RawData::Bayer)
+                .or_else(|| RawImage::new(self, rawdata.color3_image).map(RawData::Color3))
+                .or_else(|| RawImage::new(self, rawdata.color4_image).map(RawData::Color4))
+                .or_else(|| RawImage::new(self, rawdata.float_image).map(RawData::Float))
+                .or_else(|| RawImage::new(self, rawdata.float3_image).map(RawData::Float3))
+                .or_else(|| RawImage::new(self, rawdata.float4_image).map(RawData::Float4))
+        }
+        .ok_or(LibrawError::RawDataMissing)
+    }
+}
+
+impl<'p, T> RawImage<'p, T> {
+    /// # Safety
+    /// `ptr` must be null or one of the image pointers of `processor.rawdata` with pixel type `T`
+    unsafe fn new(processor: &'p Processor, ptr: *mut T) -> Option<Self> {
+        if ptr.is_null() {
+            return None;
+        }
+        let sizes = &processor.inner.as_ref().rawdata.sizes;
+        let raw_width = sizes.raw_width as usize;
+        let raw_height = sizes.raw_height as usize;
+        let pitch = match sizes.raw_pitch as usize / core::mem::size_of::<T>() {
+            0 => raw_width,
+            // Rows shorter than raw_width don't match the buffer libraw allocated
+            pitch if pitch < raw_width => return None,
+            pitch => pitch,
+        };
+        Some(RawImage {
+            processor,
+            data: std::slice::from_raw_parts(ptr, pitch * raw_height),
+            pitch,
+            raw_width,
+            raw_height,
+            width: sizes.width as usize,
+            height: sizes.height as usize,
+            top_margin: sizes.top_margin as usize,
+            left_margin: sizes.left_margin as usize,
+        })
+    }
+
+    pub fn raw_width(&self) -> usize {
+        self.raw_width
+    }
+    pub fn raw_height(&self) -> usize {
+        self.raw_height
+    }
+    /// Width of the visible area
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    /// Height of the visible area
+    pub fn height(&self) -> usize {
+        self.height
+    }
+    pub fn top_margin(&self) -> usize {
+        self.top_margin
+    }
+    pub fn left_margin(&self) -> usize {
+        self.left_margin
+    }
+
+    /// The whole buffer including any padding at the end of the rows
+    pub fn as_slice(&self) -> &'p [T] {
+        self.data
+    }
+
+    /// The pixel at (row, col) of the full sensor area
+    pub fn get(&self, row: usize, col: usize) -> Option<&'p T> {
+        self.row(row)?.get(col)
+    }
+
+    /// The pixel at (row, col) of the visible area
+    pub fn visible(&self, row: usize, col: usize) -> Option<&'p T> {
+        self.visible_row(row)?.get(col)
+    }
+
+    /// A row of the full sensor area
+    pub fn row(&self, row: usize) -> Option<&'p [T]> {
+        if row >= self.raw_height {
+            return None;
+        }
+        let start = row * self.pitch;
+        self.data.get(start..start + self.raw_width)
+    }
+
+    /// A row of the visible area
+    pub fn visible_row(&self, row: usize) -> Option<&'p [T]> {
+        if row >= self.height {
+            return None;
+        }
+        let start = self.left_margin.min(self.raw_width);
+        let end = (self.left_margin + self.width).min(self.raw_width);
+        self.row(self.top_margin + row).map(|r| &r[start..end])
+    }
+
+    /// Iterate over the rows of the full sensor area
+    pub fn rows(&self) -> impl Iterator<Item = &'p [T]> + 'p {
+        let this = *self;
+        (0..self.raw_height).filter_map(move |row| this.row(row))
+    }
+
+    /// Iterate over the rows of the visible area
+    pub fn visible_rows(&self) -> impl Iterator<Item = &'p [T]> + 'p {
+        let this = *self;
+        (0..self.height).filter_map(move |row| this.visible_row(row))
+    }
+}
+
+impl<'p> RawImage<'p, u16> {
+    /// The color index of the pixel at (row, col) of the visible area as returned by libraw_COLOR
+    ///
+    /// The index can be used with `idata.cdesc` to get the color
+    pub fn color(&self, row: usize, col: usize) -> i32 {
+        unsafe {
+            sys::libraw_COLOR(
+                self.processor.inner.as_ptr(),
+                row as libc::c_int,
+                col as libc::c_int,
+            )
+        }
+    }
+
+    /// The color of the pixel at (row, col) of the visible area as a character from `idata.cdesc`
+    pub fn color_char(&self, row: usize, col: usize) -> Option<char> {
+        let index = usize::try_from(self.color(row, col)).ok()?;
+        self.processor.idata().cdesc.as_ascii().chars().nth(index)
+    }
+
+    /// The bayer pattern of the visible area
+    ///
+    /// Returns [`None`] for non bayer sensors (X-Trans, foveon, monochrome, ...)
+    pub fn cfa_pattern(&self) -> Option<CfaPattern> {
+        // filters < 1000 are special layouts (leaf, xtrans), 0 is no cfa at all
+        if self.processor.idata().filters < 1000 {
+            return None;
+        }
+        CfaPattern::from_colors([
+            [self.color_char(0, 0)?, self.color_char(0, 1)?],
+            [self.color_char(1, 0)?, self.color_char(1, 1)?],
+        ])
+    }
+}
+
+/// A borrowed view over the 4 channel `image` buffer filled by `raw2image`
+///
+/// The buffer is `iwidth` x `iheight` pixels, which is half of the visible area in each dimension
+/// when `half_size` is set. Each pixel has one slot per color index, only the slot for the color
+/// of the pixel is filled unless the image has been processed.
+#[derive(Debug, Clone, Copy)]
+pub struct Image4<'p> {
+    data: &'p [[u16; 4]],
+    width: usize,
+    height: usize,
+    half_size: bool,
+}
+
+impl<'p> Image4<'p> {
+    /// sizes.iwidth
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    /// sizes.iheight
+    pub fn height(&self) -> usize {
+        self.height
+    }
+    /// Whether the buffer was built with half_size (every pixel is a whole 2x2 bayer block)
+    pub fn is_half_size(&self) -> bool {
+        self.half_size
+    }
+    pub fn as_slice(&self) -> &'p [[u16; 4]] {
+        self.data
+    }
+    pub fn get(&self, row: usize, col: usize) -> Option<&'p [u16; 4]> {
+        self.row(row)?.get(col)
+    }
+    pub fn row(&self, row: usize) -> Option<&'p [[u16; 4]]> {
+        if row >= self.height {
+            return None;
+        }
+        self.data.get(row * self.width..(row + 1) * self.width)
+    }
+    pub fn rows(&self) -> impl Iterator<Item = &'p [[u16; 4]]> + 'p {
+        self.data.chunks_exact(self.width.max(1)).take(self.height)
+    }
+}
+
+impl Processor {
+    /// Calls libraw_raw2image
+    ///
+    /// Fills the 4 channel `image` buffer from the unpacked raw data, call after `unpack`.
+    ///
+    /// Note: libraw_dcraw_process rebuilds `image` from the raw data so changes made to it here
+    /// are not seen by `dcraw_process`
+    pub fn raw2image(&mut self) -> Result<(), LibrawError> {
+        LibrawError::check(unsafe { sys::libraw_raw2image(self.inner.as_ptr()) })
+    }
+
+    /// Calls libraw_subtract_black
+    ///
+    /// Subtracts the black level from the `image` buffer, call after `raw2image`
+    pub fn subtract_black(&mut self) -> Result<(), LibrawError> {
+        if unsafe { self.inner.as_ref().image.is_null() } {
+            return Err(crate::error::InternalLibrawError::OutOfOrderCall.into());
+        }
+        unsafe { sys::libraw_subtract_black(self.inner.as_ptr()) };
+        Ok(())
+    }
+
+    /// Calls libraw_free_image and releases the `image` buffer
+    pub fn free_image(&mut self) {
+        unsafe { sys::libraw_free_image(self.inner.as_ptr()) };
+    }
+
+    /// Get a view over the 4 channel `image` buffer
+    ///
+    /// Returns [`LibrawError::RawDataMissing`] if neither `raw2image` nor `dcraw_process` has
+    /// been called
+    pub fn image4(&self) -> Result<Image4<'_>, LibrawError> {
+        let inner = unsafe { self.inner.as_ref() };
+        if inner.image.is_null() {
+            return Err(LibrawError::RawDataMissing);
+        }
+        let width = inner.sizes.iwidth as usize;
+        let height = inner.sizes.iheight as usize;
+        Ok(Image4 {
+            data: unsafe { std::slice::from_raw_parts(inner.image, width * height) },
+            width,
+            height,
+            // libraw only shrinks bayer data, so the option alone doesn't say if it did
+            half_size: inner.sizes.iwidth < inner.sizes.width,
+        })
+    }
+
+    /// Get the 4 channel `image` buffer mutably, see [`Processor::image4`]
+    pub fn image4_mut(&mut self) -> Result<&mut [[u16; 4]], LibrawError> {
+        let inner = unsafe { self.inner.as_mut() };
+        if inner.image.is_null() {
+            return Err(LibrawError::RawDataMissing);
+        }
+        let len = inner.sizes.iwidth as usize * inner.sizes.iheight as usize;
+        Ok(unsafe { std::slice::from_raw_parts_mut(inner.image, len) })
+    }
+}
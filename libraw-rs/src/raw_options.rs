@@ -0,0 +1,331 @@
+//! Typed raw unpack parameters (libraw_raw_unpack_params_t)
+//!
+//! These control how libraw opens and unpacks the file, as opposed to
+//! [`crate::ProcessingOptions`] which control the processing afterwards.
+
+use std::ffi::{CStr, CString};
+
+use crate::{sys, LibrawError, Processor, ProcessorBuilder};
+
+bitflags::bitflags! {
+    /// libraw_raw_unpack_params_t::options
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct RawFlags: u32 {
+        /// Unpack every frame of Pentax pixel shift files
+        const PENTAX_PS_ALL_FRAMES = sys::LibRaw_processing_options_LIBRAW_RAWOPTIONS_PENTAX_PS_ALLFRAMES;
+        /// Convert floating point DNGs to integers
+        const CONVERT_FLOAT_TO_INT = sys::LibRaw_processing_options_LIBRAW_RAWOPTIONS_CONVERTFLOAT_TO_INT;
+        const ARQ_SKIP_CHANNEL_SWAP = sys::LibRaw_processing_options_LIBRAW_RAWOPTIONS_ARQ_SKIP_CHANNEL_SWAP;
+        const NO_ROTATE_FOR_KODAK_THUMBNAILS = sys::LibRaw_processing_options_LIBRAW_RAWOPTIONS_NO_ROTATE_FOR_KODAK_THUMBNAILS;
+        const USE_PPM16_THUMBS = sys::LibRaw_processing_options_LIBRAW_RAWOPTIONS_USE_PPM16_THUMBS;
+        const DONT_CHECK_DNG_ILLUMINANT = sys::LibRaw_processing_options_LIBRAW_RAWOPTIONS_DONT_CHECK_DNG_ILLUMINANT;
+        const DNGSDK_ZEROCOPY = sys::LibRaw_processing_options_LIBRAW_RAWOPTIONS_DNGSDK_ZEROCOPY;
+        const ZERO_FILTERS_FOR_MONOCHROME_TIFFS = sys::LibRaw_processing_options_LIBRAW_RAWOPTIONS_ZEROFILTERS_FOR_MONOCHROMETIFFS;
+        /// Also list the enhanced image of DNG files
+        const DNG_ADD_ENHANCED = sys::LibRaw_processing_options_LIBRAW_RAWOPTIONS_DNG_ADD_ENHANCED;
+        /// Also list the previews of DNG files as raw images
+        const DNG_ADD_PREVIEWS = sys::LibRaw_processing_options_LIBRAW_RAWOPTIONS_DNG_ADD_PREVIEWS;
+        const DNG_PREFER_LARGEST_IMAGE = sys::LibRaw_processing_options_LIBRAW_RAWOPTIONS_DNG_PREFER_LARGEST_IMAGE;
+        /// Apply the DNG stage 2 processing (linearization, black / white level)
+        const DNG_STAGE2 = sys::LibRaw_processing_options_LIBRAW_RAWOPTIONS_DNG_STAGE2;
+        /// Apply the DNG stage 3 processing (demosaiced data)
+        const DNG_STAGE3 = sys::LibRaw_processing_options_LIBRAW_RAWOPTIONS_DNG_STAGE3;
+        const DNG_ALLOW_SIZE_CHANGE = sys::LibRaw_processing_options_LIBRAW_RAWOPTIONS_DNG_ALLOWSIZECHANGE;
+        const DNG_DISABLE_WB_ADJUST = sys::LibRaw_processing_options_LIBRAW_RAWOPTIONS_DNG_DISABLEWBADJUST;
+        const PROVIDE_NONSTANDARD_WB = sys::LibRaw_processing_options_LIBRAW_RAWOPTIONS_PROVIDE_NONSTANDARD_WB;
+        const CAMERA_WB_FALLBACK_TO_DAYLIGHT = sys::LibRaw_processing_options_LIBRAW_RAWOPTIONS_CAMERAWB_FALLBACK_TO_DAYLIGHT;
+        const CHECK_THUMBNAILS_KNOWN_VENDORS = sys::LibRaw_processing_options_LIBRAW_RAWOPTIONS_CHECK_THUMBNAILS_KNOWN_VENDORS;
+        const CHECK_THUMBNAILS_ALL_VENDORS = sys::LibRaw_processing_options_LIBRAW_RAWOPTIONS_CHECK_THUMBNAILS_ALL_VENDORS;
+        /// Stage 2 only if the file has the opcodes for it
+        const DNG_STAGE2_IF_PRESENT = sys::LibRaw_processing_options_LIBRAW_RAWOPTIONS_DNG_STAGE2_IFPRESENT;
+        /// Stage 3 only if the file has the opcodes for it
+        const DNG_STAGE3_IF_PRESENT = sys::LibRaw_processing_options_LIBRAW_RAWOPTIONS_DNG_STAGE3_IFPRESENT;
+        const DNG_ADD_MASKS = sys::LibRaw_processing_options_LIBRAW_RAWOPTIONS_DNG_ADD_MASKS;
+        const CANON_IGNORE_MAKERNOTES_ROTATION = sys::LibRaw_processing_options_LIBRAW_RAWOPTIONS_CANON_IGNORE_MAKERNOTES_ROTATION;
+    }
+}
+
+impl Default for RawFlags {
+    /// Same as libraw
+    fn default() -> Self {
+        RawFlags::CONVERT_FLOAT_TO_INT | RawFlags::ZERO_FILTERS_FOR_MONOCHROME_TIFFS
+    }
+}
+
+bitflags::bitflags! {
+    /// libraw_raw_unpack_params_t::specials
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct RawSpecials: u32 {
+        /// Only the base values of Sony ARW2, skips the posterized deltas
+        const SONY_ARW2_BASE_ONLY = sys::LibRaw_rawspecial_t_LIBRAW_RAWSPECIAL_SONYARW2_BASEONLY;
+        const SONY_ARW2_DELTA_ONLY = sys::LibRaw_rawspecial_t_LIBRAW_RAWSPECIAL_SONYARW2_DELTAONLY;
+        const SONY_ARW2_DELTA_ZERO_BASE = sys::LibRaw_rawspecial_t_LIBRAW_RAWSPECIAL_SONYARW2_DELTAZEROBASE;
+        const SONY_ARW2_DELTA_TO_VALUE = sys::LibRaw_rawspecial_t_LIBRAW_RAWSPECIAL_SONYARW2_DELTATOVALUE;
+        const NO_DP2Q_INTERPOLATE_RG = sys::LibRaw_rawspecial_t_LIBRAW_RAWSPECIAL_NODP2Q_INTERPOLATERG;
+        const NO_DP2Q_INTERPOLATE_AF = sys::LibRaw_rawspecial_t_LIBRAW_RAWSPECIAL_NODP2Q_INTERPOLATEAF;
+        /// Keep Canon sRAW in YCbCr
+        const SRAW_NO_RGB = sys::LibRaw_rawspecial_t_LIBRAW_RAWSPECIAL_SRAW_NO_RGB;
+        /// Don't interpolate the chroma of Canon sRAW
+        const SRAW_NO_INTERPOLATE = sys::LibRaw_rawspecial_t_LIBRAW_RAWSPECIAL_SRAW_NO_INTERPOLATE;
+    }
+}
+
+/// libraw_raw_unpack_params_t with typed values
+///
+/// Set them with [`ProcessorBuilder::with_raw_options`] or [`Processor::set_raw_options`], the
+/// processor writes them again after every open / recycle.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawOptions {
+    shot_select: u32,
+    max_raw_memory_mb: u32,
+    flags: RawFlags,
+    specials: RawSpecials,
+    sony_arw2_posterization_threshold: i32,
+    coolscan_nef_gamma: f32,
+    custom_cameras: Vec<String>,
+}
+
+impl Default for RawOptions {
+    fn default() -> Self {
+        Self {
+            shot_select: 0,
+            max_raw_memory_mb: sys::LIBRAW_MAX_ALLOC_MB_DEFAULT,
+            flags: RawFlags::default(),
+            specials: RawSpecials::default(),
+            sony_arw2_posterization_threshold: 0,
+            coolscan_nef_gamma: 1.0,
+            custom_cameras: Vec::new(),
+        }
+    }
+}
+
+impl RawOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Which image of multi image files to open
+    pub fn with_shot_select(mut self, shot: u32) -> Self {
+        self.shot_select = shot;
+        self
+    }
+
+    /// Refuse to unpack files which need more memory than this
+    pub fn with_max_raw_memory_mb(mut self, megabytes: u32) -> Self {
+        self.max_raw_memory_mb = megabytes;
+        self
+    }
+
+    /// Replace all the flags
+    pub fn with_flags(mut self, flags: RawFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Set or clear some of the flags
+    pub fn set_flags(mut self, flags: RawFlags, enabled: bool) -> Self {
+        self.flags.set(flags, enabled);
+        self
+    }
+
+    /// DNG stage 2 / 3 processing, `if_present` only applies the stages the file has opcodes for
+    pub fn with_dng_stages(mut self, stage2: bool, stage3: bool, if_present: bool) -> Self {
+        let (flag2, flag3) = if if_present {
+            (
+                RawFlags::DNG_STAGE2_IF_PRESENT,
+                RawFlags::DNG_STAGE3_IF_PRESENT,
+            )
+        } else {
+            (RawFlags::DNG_STAGE2, RawFlags::DNG_STAGE3)
+        };
+        self.flags.remove(
+            RawFlags::DNG_STAGE2
+                | RawFlags::DNG_STAGE3
+                | RawFlags::DNG_STAGE2_IF_PRESENT
+                | RawFlags::DNG_STAGE3_IF_PRESENT,
+        );
+        self.flags.set(flag2, stage2);
+        self.flags.set(flag3, stage3);
+        self
+    }
+
+    pub fn with_specials(mut self, specials: RawSpecials) -> Self {
+        self.specials = specials;
+        self
+    }
+
+    /// Values below the threshold skip the Sony ARW2 posterization fix, 0 to disable
+    pub fn with_sony_arw2_posterization_threshold(mut self, threshold: i32) -> Self {
+        self.sony_arw2_posterization_threshold = threshold;
+        self
+    }
+
+    /// Gamma of Nikon Coolscan NEF files
+    pub fn with_coolscan_nef_gamma(mut self, gamma: f32) -> Self {
+        self.coolscan_nef_gamma = gamma;
+        self
+    }
+
+    /// Add a camera which libraw doesn't know about
+    ///
+    /// The string has the same format as the entries of libraw's custom camera table:
+    /// `fsize,rw,rh,lm,tm,rm,bm,lf,nf,ofs,bps,mask,make,model,offset`
+    pub fn with_custom_camera(mut self, camera: impl Into<String>) -> Self {
+        self.custom_cameras.push(camera.into());
+        self
+    }
+
+    pub fn shot_select(&self) -> u32 {
+        self.shot_select
+    }
+
+    pub fn max_raw_memory_mb(&self) -> u32 {
+        self.max_raw_memory_mb
+    }
+
+    pub fn flags(&self) -> RawFlags {
+        self.flags
+    }
+
+    pub fn specials(&self) -> RawSpecials {
+        self.specials
+    }
+
+    pub fn sony_arw2_posterization_threshold(&self) -> i32 {
+        self.sony_arw2_posterization_threshold
+    }
+
+    pub fn coolscan_nef_gamma(&self) -> f32 {
+        self.coolscan_nef_gamma
+    }
+
+    pub fn custom_cameras(&self) -> &[String] {
+        &self.custom_cameras
+    }
+
+    /// Check the values which libraw would silently misbehave on
+    pub fn validate(&self) -> Result<(), LibrawError> {
+        if self.max_raw_memory_mb == 0 {
+            return Err(LibrawError::InvalidOption(
+                "max raw memory must be greater than 0",
+            ));
+        }
+        if self.sony_arw2_posterization_threshold < 0 {
+            return Err(LibrawError::InvalidOption(
+                "sony arw2 posterization threshold must not be negative",
+            ));
+        }
+        if !self.coolscan_nef_gamma.is_finite() || self.coolscan_nef_gamma <= 0.0 {
+            return Err(LibrawError::InvalidOption(
+                "coolscan nef gamma must be positive",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// [`RawOptions`] together with the strings libraw points to
+#[derive(Debug)]
+pub(crate) struct AppliedRawOptions {
+    options: RawOptions,
+    _strings: Vec<CString>,
+    /// Null terminated
+    pointers: Vec<*mut libc::c_char>,
+}
+
+impl AppliedRawOptions {
+    pub(crate) fn new(options: RawOptions) -> Result<Self, LibrawError> {
+        options.validate()?;
+        let strings = options
+            .custom_cameras
+            .iter()
+            .map(|camera| CString::new(camera.as_str()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let pointers = strings
+            .iter()
+            .map(|s| s.as_ptr() as *mut libc::c_char)
+            .chain(core::iter::once(core::ptr::null_mut()))
+            .collect();
+        Ok(Self {
+            options,
+            _strings: strings,
+            pointers,
+        })
+    }
+
+    /// Keep the frame chosen with [`Processor::select_frame`] when the options are written again
+    pub(crate) fn select_shot(&mut self, shot: u32) {
+        self.options.shot_select = shot;
+    }
+
+    pub(crate) fn write(&mut self, rawparams: &mut sys::libraw_raw_unpack_params_t) {
+        let options = &self.options;
+        rawparams.shot_select = options.shot_select;
+        rawparams.max_raw_memory_mb = options.max_raw_memory_mb;
+        rawparams.options = options.flags.bits();
+        rawparams.specials = options.specials.bits();
+        rawparams.sony_arw2_posterization_thr = options.sony_arw2_posterization_threshold;
+        rawparams.coolscan_nef_gamma = options.coolscan_nef_gamma;
+        rawparams.custom_camera_strings = if options.custom_cameras.is_empty() {
+            core::ptr::null_mut()
+        } else {
+            self.pointers.as_mut_ptr()
+        };
+    }
+}
+
+impl ProcessorBuilder {
+    /// Validate `options` and write them to the raw params of the processor
+    pub fn with_raw_options(mut self, options: RawOptions) -> Result<Self, LibrawError> {
+        let mut applied = AppliedRawOptions::new(options)?;
+        applied.write(unsafe { &mut self.inner.as_mut().rawparams });
+        self.raw_options = Some(applied);
+        Ok(self)
+    }
+}
+
+impl Processor {
+    /// Validate `options` and write them to libraw_raw_unpack_params_t
+    ///
+    /// They only take effect on the next open
+    pub fn set_raw_options(&mut self, options: RawOptions) -> Result<(), LibrawError> {
+        let mut applied = AppliedRawOptions::new(options)?;
+        applied.write(self.rawparams());
+        self.raw_options = Some(applied);
+        Ok(())
+    }
+
+    /// Read the current libraw_raw_unpack_params_t back as [`RawOptions`]
+    pub fn raw_options(&self) -> RawOptions {
+        let rawparams = unsafe { &self.inner.as_ref().rawparams };
+        let mut custom_cameras = Vec::new();
+        let mut camera = rawparams.custom_camera_strings;
+        while !camera.is_null() && !unsafe { *camera }.is_null() {
+            let string = unsafe { CStr::from_ptr(*camera) };
+            custom_cameras.push(string.to_string_lossy().into_owned());
+            camera = unsafe { camera.add(1) };
+        }
+        RawOptions {
+            shot_select: rawparams.shot_select,
+            max_raw_memory_mb: rawparams.max_raw_memory_mb,
+            flags: RawFlags::from_bits_retain(rawparams.options),
+            specials: RawSpecials::from_bits_retain(rawparams.specials),
+            sony_arw2_posterization_threshold: rawparams.sony_arw2_posterization_thr,
+            coolscan_nef_gamma: rawparams.coolscan_nef_gamma,
+            custom_cameras,
+        }
+    }
+
+    /// Get the raw unpack parameters
+    pub fn rawparams(&mut self) -> &mut sys::libraw_raw_unpack_params_t {
+        unsafe { &mut self.inner.as_mut().rawparams }
+    }
+
+    /// Write the options set through [`Processor::set_raw_options`] again
+    pub(crate) fn restore_raw_options(&mut self) {
+        let rawparams = unsafe { &mut self.inner.as_mut().rawparams };
+        if let Some(applied) = &mut self.raw_options {
+            applied.write(rawparams);
+        }
+    }
+}
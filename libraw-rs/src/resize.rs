@@ -0,0 +1,175 @@
+//! Resizing of the processed image
+//!
+//! When the target is at most half the size of the sensor [`Resize::auto_half_size`] lets libraw
+//! demosaic at half size (`half_size`), which is several times faster and loses nothing after
+//! the downscale.
+
+use image::imageops::FilterType;
+use image::DynamicImage;
+
+use crate::{LibrawError, Processor};
+
+/// How the image is fit into the target size
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResizeMode {
+    /// Keep the aspect ratio, the image fits within the target
+    #[default]
+    Fit,
+    /// Keep the aspect ratio, the image covers the target and the rest is cropped from the
+    /// center
+    Fill,
+    /// Stretch to exactly the target size
+    Exact,
+}
+
+/// Resampling filter
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResizeFilter {
+    Nearest,
+    /// Linear
+    Triangle,
+    /// Cubic
+    CatmullRom,
+    #[default]
+    Lanczos3,
+}
+
+impl From<ResizeFilter> for FilterType {
+    fn from(filter: ResizeFilter) -> Self {
+        match filter {
+            ResizeFilter::Nearest => FilterType::Nearest,
+            ResizeFilter::Triangle => FilterType::Triangle,
+            ResizeFilter::CatmullRom => FilterType::CatmullRom,
+            ResizeFilter::Lanczos3 => FilterType::Lanczos3,
+        }
+    }
+}
+
+/// Unsharp mask applied after resizing
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sharpen {
+    /// Radius of the blur
+    pub sigma: f32,
+    /// Minimum difference from the blurred image to sharpen
+    pub threshold: i32,
+}
+
+impl Default for Sharpen {
+    fn default() -> Self {
+        Self {
+            sigma: 0.5,
+            threshold: 0,
+        }
+    }
+}
+
+/// Resize the processed image to a target size
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Resize {
+    pub width: u32,
+    pub height: u32,
+    pub mode: ResizeMode,
+    pub filter: ResizeFilter,
+    pub sharpen: Option<Sharpen>,
+    /// Process with `half_size` when the target is at most half the size of the sensor
+    pub auto_half_size: bool,
+}
+
+impl Resize {
+    /// Fit into `width` x `height` with [`ResizeFilter::Lanczos3`]
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            mode: ResizeMode::default(),
+            filter: ResizeFilter::default(),
+            sharpen: None,
+            auto_half_size: true,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), LibrawError> {
+        if self.width == 0 || self.height == 0 {
+            return Err(LibrawError::InvalidOption("resize target can't be empty"));
+        }
+        if let Some(sharpen) = self.sharpen {
+            if !(sharpen.sigma.is_finite() && sharpen.sigma > 0.0) {
+                return Err(LibrawError::InvalidOption("sharpen sigma must be positive"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Horizontal and vertical scale for an image of `width` x `height`
+    fn scale(&self, width: u32, height: u32) -> (f64, f64) {
+        let x = self.width as f64 / width.max(1) as f64;
+        let y = self.height as f64 / height.max(1) as f64;
+        match self.mode {
+            ResizeMode::Fit => (x.min(y), x.min(y)),
+            ResizeMode::Fill => (x.max(y), x.max(y)),
+            ResizeMode::Exact => (x, y),
+        }
+    }
+
+    /// Size of an image of `width` x `height` after resizing
+    pub fn output_size(&self, width: u32, height: u32) -> (u32, u32) {
+        match self.mode {
+            ResizeMode::Fit => {
+                let (scale, _) = self.scale(width, height);
+                let size = |v: u32| ((v as f64 * scale).round() as u32).max(1);
+                (size(width), size(height))
+            }
+            ResizeMode::Fill | ResizeMode::Exact => (self.width, self.height),
+        }
+    }
+
+    /// Whether an image of `width` x `height` is shrunk to at most half its size
+    pub fn allows_half_size(&self, width: u32, height: u32) -> bool {
+        let (x, y) = self.scale(width, height);
+        x <= 0.5 && y <= 0.5
+    }
+
+    /// Resize and sharpen `image`
+    pub fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let filter = FilterType::from(self.filter);
+        let resized = match self.mode {
+            ResizeMode::Fit => image.resize(self.width, self.height, filter),
+            ResizeMode::Fill => image.resize_to_fill(self.width, self.height, filter),
+            ResizeMode::Exact => image.resize_exact(self.width, self.height, filter),
+        };
+        match self.sharpen {
+            Some(sharpen) => resized.unsharpen(sharpen.sigma, sharpen.threshold),
+            None => resized,
+        }
+    }
+}
+
+impl Processor {
+    /// Whether processing for `resize` can use `half_size`
+    ///
+    /// Compares against the size of the sensor after the flip, call after `open`.
+    pub fn wants_half_size(&self, resize: &Resize) -> bool {
+        let sizes = self.sizes();
+        let params = unsafe { &self.inner.as_ref().params };
+        let flip = if params.user_flip >= 0 {
+            params.user_flip
+        } else {
+            sizes.flip
+        };
+        let (width, height) = (sizes.width as u32, sizes.height as u32);
+        let (width, height) = if flip & 4 != 0 {
+            (height, width)
+        } else {
+            (width, height)
+        };
+        resize.auto_half_size && params.half_size == 0 && resize.allows_half_size(width, height)
+    }
+
+    /// Process the raw (unpacking it first if needed) and resize it
+    pub fn process_resized(&mut self, resize: &Resize) -> Result<DynamicImage, LibrawError> {
+        resize.validate()?;
+        let half_size = self.wants_half_size(resize);
+        let image = self.process_image(half_size)?;
+        Ok(resize.apply(DynamicImage::try_from(image)?))
+    }
+}
@@ -0,0 +1,231 @@
+//! Embedded previews
+//!
+//! Most raw files carry more than one preview (eg. a tiny, a medium and a full size jpeg), libraw
+//! lists them in `thumbs_list` and unpacks a specific one with `unpack_thumb_ex`.
+
+use crate::{Flip, IntoResolution, LibrawError, Processor, ThumbnailFormat};
+
+/// An entry of libraw_thumbnail_list_t
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThumbnailInfo {
+    /// Index to pass to `unpack_thumb_ex`
+    pub index: usize,
+    pub format: ThumbnailFormat,
+    pub width: u16,
+    pub height: u16,
+    /// Orientation of this preview, [`None`] if libraw doesn't know it
+    pub flip: Option<Flip>,
+    /// Size of the preview in the file in bytes
+    pub length: u32,
+    /// Offset of the preview in the file
+    pub offset: i64,
+}
+
+impl ThumbnailInfo {
+    fn from_item(index: usize, item: &sys::libraw_thumbnail_item_t) -> Self {
+        Self {
+            index,
+            format: ThumbnailFormat::from_internal(item.tformat),
+            width: item.twidth,
+            height: item.theight,
+            // libraw uses 0xffff for unknown
+            flip: (item.tflip != 0xffff).then(|| Flip::from(item.tflip as i32)),
+            length: item.tlength,
+            offset: item.toffset,
+        }
+    }
+
+    /// Width and height after the orientation has been applied
+    pub fn oriented_size(&self) -> (u32, u32) {
+        match self.flip {
+            // flip 5 and 6 are rotations by 90 degrees
+            Some(flip) if flip.0 & 4 != 0 => (self.height as u32, self.width as u32),
+            _ => (self.width as u32, self.height as u32),
+        }
+    }
+}
+
+impl ThumbnailFormat {
+    fn from_internal(format: sys::LibRaw_internal_thumbnail_formats) -> Self {
+        use ThumbnailFormat::*;
+        match format {
+            sys::LibRaw_internal_thumbnail_formats_LIBRAW_INTERNAL_THUMBNAIL_JPEG => Jpeg,
+            sys::LibRaw_internal_thumbnail_formats_LIBRAW_INTERNAL_THUMBNAIL_PPM
+            | sys::LibRaw_internal_thumbnail_formats_LIBRAW_INTERNAL_THUMBNAIL_KODAK_THUMB
+            | sys::LibRaw_internal_thumbnail_formats_LIBRAW_INTERNAL_THUMBNAIL_KODAK_YCBCR
+            | sys::LibRaw_internal_thumbnail_formats_LIBRAW_INTERNAL_THUMBNAIL_KODAK_RGB
+            | sys::LibRaw_internal_thumbnail_formats_LIBRAW_INTERNAL_THUMBNAIL_X3F => Bitmap,
+            sys::LibRaw_internal_thumbnail_formats_LIBRAW_INTERNAL_THUMBNAIL_PPM16 => Bitmap16,
+            sys::LibRaw_internal_thumbnail_formats_LIBRAW_INTERNAL_THUMBNAIL_LAYER => Layer,
+            sys::LibRaw_internal_thumbnail_formats_LIBRAW_INTERNAL_THUMBNAIL_ROLLEI => Rollei,
+            _ => Unknown,
+        }
+    }
+}
+
+impl Processor {
+    /// Iterate over the previews embedded in the file
+    pub fn thumbnails(&self) -> impl Iterator<Item = ThumbnailInfo> + '_ {
+        let list = self.thumbs_list();
+        let count = (list.thumbcount.max(0) as usize).min(list.thumblist.len());
+        list.thumblist[..count]
+            .iter()
+            .enumerate()
+            .map(|(index, item)| ThumbnailInfo::from_item(index, item))
+    }
+
+    /// Unpack the preview described by `info`
+    pub fn unpack_thumbnail(&mut self, info: &ThumbnailInfo) -> Result<(), LibrawError> {
+        self.unpack_thumb_ex(info.index as libc::c_int)
+    }
+
+    /// The entry of the preview libraw currently selects (`thumbnail`), the default one unless
+    /// another one has been unpacked
+    pub fn current_thumbnail(&self) -> Option<ThumbnailInfo> {
+        let thumbnail = self.thumbnail();
+        self.thumbnails().find(|t| {
+            (t.width, t.height, t.length)
+                == (thumbnail.twidth, thumbnail.theight, thumbnail.tlength)
+        })
+    }
+
+    /// Pick the smallest preview which is at least as large as `target` in both dimensions (after
+    /// applying its orientation) and unpack it
+    ///
+    /// Falls back to the largest preview if none of them are large enough. The picked preview
+    /// stays selected, so [`Processor::thumbnail`] and `get_jpeg` return it until another one is
+    /// unpacked (eg. the one from [`Processor::current_thumbnail`] before this call).
+    pub fn best_thumbnail(
+        &mut self,
+        target: impl IntoResolution,
+    ) -> Result<ThumbnailInfo, LibrawError> {
+        let target = target.into_resolution();
+        let area = |t: &ThumbnailInfo| t.width as u64 * t.height as u64;
+        let thumbnails = self.thumbnails().collect::<Vec<_>>();
+        let best = thumbnails
+            .iter()
+            .filter(|t| {
+                let (width, height) = t.oriented_size();
+                width >= target.width && height >= target.height
+            })
+            .min_by_key(|t| area(t))
+            .or_else(|| thumbnails.iter().max_by_key(|t| area(t)))
+            .copied()
+            .ok_or(crate::error::InternalLibrawError::NoThumbnail)?;
+        self.unpack_thumbnail(&best)?;
+        Ok(best)
+    }
+}
+
+#[cfg(feature = "jpeg")]
+impl Processor {
+    /// Unpack the preview picked by [`Processor::best_thumbnail`] and return it as a jpeg with
+    /// the orientation of that preview
+    ///
+    /// The previously selected preview is unpacked again afterwards so `get_jpeg` isn't affected
+    pub fn best_jpeg(&mut self, target: impl IntoResolution) -> Result<Vec<u8>, LibrawError> {
+        let previous = self.current_thumbnail();
+        let info = self.best_thumbnail(target)?;
+        // Fall back to the orientation of the raw if libraw doesn't know the one of the preview
+        let flip = info.flip.unwrap_or_else(|| Flip::from(self.sizes().flip));
+        let jpeg = self.thumbnail_jpeg(Some(crate::Orientation::from(flip)))?;
+        if let Some(previous) = previous.filter(|previous| previous.index != info.index) {
+            self.unpack_thumbnail(&previous)?;
+        }
+        Ok(jpeg)
+    }
+}
+
+/// A decoded thumbnail, see [`Processor::decode_thumbnail`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Thumbnail {
+    pub width: u32,
+    pub height: u32,
+    pub data: ThumbnailData,
+}
+
+/// Pixels / bitstream of a [`Thumbnail`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ThumbnailData {
+    /// The embedded jpeg as-is
+    Jpeg(Vec<u8>),
+    /// Interleaved 8 bit rgb
+    Rgb8(Vec<u8>),
+    /// Interleaved 16 bit rgb
+    Rgb16(Vec<u16>),
+    /// The H265 preview of CR3 files wrapped in a HEIF container
+    Heif(Vec<u8>),
+}
+
+impl Thumbnail {
+    /// Convert the bitmap variants to 8 bit rgb, jpeg and heif previews are returned as-is
+    pub fn into_rgb8(self) -> Self {
+        match self.data {
+            ThumbnailData::Rgb16(data) => Self {
+                data: ThumbnailData::Rgb8(data.into_iter().map(|p| (p >> 8) as u8).collect()),
+                ..self
+            },
+            _ => self,
+        }
+    }
+}
+
+impl Processor {
+    /// Decode the unpacked thumbnail (calls unpack_thumb if no thumbnail has been unpacked yet)
+    ///
+    /// Bitmap and 16 bit bitmap previews are converted to interleaved rgb, grayscale previews are
+    /// expanded to rgb.
+    pub fn decode_thumbnail(&mut self) -> Result<Thumbnail, LibrawError> {
+        if unsafe { self.inner.as_ref().thumbnail.thumb.is_null() } {
+            self.unpack_thumb()?;
+        }
+        let thumbnail = self.thumbnail();
+        let data = unsafe {
+            std::slice::from_raw_parts(thumbnail.thumb as *const u8, thumbnail.tlength as usize)
+        };
+        let width = thumbnail.twidth as u32;
+        let height = thumbnail.theight as u32;
+        let pixels = width as usize * height as usize;
+        let colors = thumbnail.tcolors.max(1) as usize;
+        let short = || LibrawError::InvalidThumbnail("thumbnail is smaller than its dimensions");
+
+        let data = match ThumbnailFormat::from(thumbnail.tformat) {
+            ThumbnailFormat::Jpeg => ThumbnailData::Jpeg(data.to_vec()),
+            ThumbnailFormat::Bitmap => {
+                let data = data.get(..pixels * colors).ok_or_else(short)?;
+                ThumbnailData::Rgb8(to_rgb(data.chunks_exact(colors)))
+            }
+            ThumbnailFormat::Bitmap16 => {
+                let data = data.get(..pixels * colors * 2).ok_or_else(short)?;
+                let data = data
+                    .chunks_exact(2)
+                    .map(|p| u16::from_ne_bytes([p[0], p[1]]))
+                    .collect::<Vec<_>>();
+                ThumbnailData::Rgb16(to_rgb(data.chunks_exact(colors)))
+            }
+            ThumbnailFormat::H265 => {
+                ThumbnailData::Heif(crate::heif::wrap_hevc(data, width, height)?)
+            }
+            // unpack_thumb already converts layered and Rollei previews to bitmaps
+            ThumbnailFormat::Layer | ThumbnailFormat::Rollei | ThumbnailFormat::Unknown => {
+                return Err(LibrawError::UnsupportedThumbnail)
+            }
+        };
+        Ok(Thumbnail {
+            width,
+            height,
+            data,
+        })
+    }
+}
+
+/// Interleave the pixels to rgb, duplicating the gray channel of single color pixels
+fn to_rgb<'a, T: Copy + 'a>(pixels: impl Iterator<Item = &'a [T]>) -> Vec<T> {
+    let mut rgb = Vec::new();
+    for pixel in pixels {
+        for c in 0..3 {
+            rgb.push(pixel[c % pixel.len()]);
+        }
+    }
+    rgb
+}
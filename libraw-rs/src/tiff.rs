@@ -0,0 +1,252 @@
+//! Big endian tiff / EXIF IFD writer
+
+use std::collections::BTreeMap;
+use std::io::Write;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Value {
+    Byte(Vec<u8>),
+    Ascii(String),
+    Short(Vec<u16>),
+    Long(Vec<u32>),
+    Rational(Vec<(u32, u32)>),
+    Undefined(Vec<u8>),
+}
+
+impl Value {
+    fn kind(&self) -> u16 {
+        match self {
+            Value::Byte(_) => 1,
+            Value::Ascii(_) => 2,
+            Value::Short(_) => 3,
+            Value::Long(_) => 4,
+            Value::Rational(_) => 5,
+            Value::Undefined(_) => 7,
+        }
+    }
+
+    fn count(&self) -> usize {
+        match self {
+            Value::Byte(v) | Value::Undefined(v) => v.len(),
+            // Including the terminating nul
+            Value::Ascii(v) => v.len() + 1,
+            Value::Short(v) => v.len(),
+            Value::Long(v) => v.len(),
+            Value::Rational(v) => v.len(),
+        }
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        match self {
+            Value::Byte(v) | Value::Undefined(v) => v.clone(),
+            Value::Ascii(v) => {
+                let mut bytes = v.as_bytes().to_vec();
+                bytes.push(0);
+                bytes
+            }
+            Value::Short(v) => v.iter().flat_map(|v| v.to_be_bytes()).collect(),
+            Value::Long(v) => v.iter().flat_map(|v| v.to_be_bytes()).collect(),
+            Value::Rational(v) => v
+                .iter()
+                .flat_map(|(n, d)| n.to_be_bytes().into_iter().chain(d.to_be_bytes()))
+                .collect(),
+        }
+    }
+}
+
+/// Approximate a positive float with a rational
+pub(crate) fn rational(value: f64) -> (u32, u32) {
+    if !value.is_finite() || value <= 0.0 {
+        return (0, 1);
+    }
+    let denominator = if value < 400_000.0 { 10_000 } else { 1 };
+    let numerator = (value * denominator as f64).round().min(u32::MAX as f64) as u32;
+    let gcd = gcd(numerator, denominator);
+    (numerator / gcd, denominator / gcd)
+}
+
+fn gcd(mut a: u32, mut b: u32) -> u32 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a.max(1)
+}
+
+/// A single IFD, entries are kept sorted by tag
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Ifd {
+    entries: BTreeMap<u16, Value>,
+}
+
+impl Ifd {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn insert(&mut self, tag: u16, value: Value) {
+        self.entries.insert(tag, value);
+    }
+
+    /// Size of the IFD including the values which don't fit in the entries
+    pub(crate) fn size(&self) -> usize {
+        let data: usize = self
+            .entries
+            .values()
+            .map(|v| v.bytes().len())
+            .filter(|len| *len > 4)
+            .map(|len| len + len % 2)
+            .sum();
+        2 + 12 * self.entries.len() + 4 + data
+    }
+
+    /// Append the IFD to `out`, offsets are relative to the start of `out`
+    pub(crate) fn write(&self, out: &mut Vec<u8>, next: u32) {
+        let start = out.len();
+        let mut data_offset = start + 2 + 12 * self.entries.len() + 4;
+        let mut data = Vec::new();
+        out.extend_from_slice(&(self.entries.len() as u16).to_be_bytes());
+        for (tag, value) in &self.entries {
+            out.extend_from_slice(&tag.to_be_bytes());
+            out.extend_from_slice(&value.kind().to_be_bytes());
+            out.extend_from_slice(&(value.count() as u32).to_be_bytes());
+            let mut bytes = value.bytes();
+            if bytes.len() <= 4 {
+                bytes.resize(4, 0);
+                out.extend_from_slice(&bytes);
+            } else {
+                out.extend_from_slice(&(data_offset as u32).to_be_bytes());
+                if bytes.len() % 2 == 1 {
+                    bytes.push(0);
+                }
+                data_offset += bytes.len();
+                data.extend_from_slice(&bytes);
+            }
+        }
+        out.extend_from_slice(&next.to_be_bytes());
+        out.extend_from_slice(&data);
+    }
+}
+
+/// Header of a big endian tiff with the first IFD right after it
+pub(crate) const HEADER: [u8; 8] = [b'M', b'M', 0, 42, 0, 0, 0, 8];
+
+/// Point the tags of `sub_ifds` in `ifd0` at the IFDs, laid out one after the other after `ifd0`
+/// which starts at `start`
+///
+/// Returns the offset right after the last IFD. Every other entry of `ifd0` has to be inserted
+/// before since they change its size.
+pub(crate) fn link_ifds(ifd0: &mut Ifd, sub_ifds: &[(u16, Ifd)], start: usize) -> usize {
+    // The sizes don't depend on the values of the pointers so insert placeholders first
+    for (tag, _) in sub_ifds {
+        ifd0.insert(*tag, Value::Long(vec![0]));
+    }
+    let mut offset = start + ifd0.size();
+    for (tag, ifd) in sub_ifds {
+        ifd0.insert(*tag, Value::Long(vec![offset as u32]));
+        offset += ifd.size();
+    }
+    offset
+}
+
+/// Samples of an uncompressed image
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Samples<'a> {
+    U8(&'a [u8]),
+    U16(&'a [u16]),
+}
+
+impl Samples<'_> {
+    /// Write the samples, 16 bit ones in big endian
+    pub(crate) fn write_be(&self, out: &mut impl Write) -> std::io::Result<()> {
+        match self {
+            Samples::U8(samples) => out.write_all(samples),
+            Samples::U16(samples) => {
+                let mut buffer = Vec::with_capacity(64 * 1024);
+                for chunk in samples.chunks(32 * 1024) {
+                    buffer.clear();
+                    buffer.extend(chunk.iter().flat_map(|s| s.to_be_bytes()));
+                    out.write_all(&buffer)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+const IMAGE_WIDTH: u16 = 0x0100;
+const IMAGE_LENGTH: u16 = 0x0101;
+const BITS_PER_SAMPLE: u16 = 0x0102;
+const COMPRESSION: u16 = 0x0103;
+const PHOTOMETRIC_INTERPRETATION: u16 = 0x0106;
+const STRIP_OFFSETS: u16 = 0x0111;
+const SAMPLES_PER_PIXEL: u16 = 0x0115;
+const ROWS_PER_STRIP: u16 = 0x0116;
+const STRIP_BYTE_COUNTS: u16 = 0x0117;
+const X_RESOLUTION: u16 = 0x011a;
+const Y_RESOLUTION: u16 = 0x011b;
+const PLANAR_CONFIGURATION: u16 = 0x011c;
+const RESOLUTION_UNIT: u16 = 0x0128;
+/// ICC profile
+pub(crate) const INTER_COLOR_PROFILE: u16 = 0x8773;
+
+/// Write an uncompressed gray or rgb image as a single strip tiff
+///
+/// `ifd0` holds any extra tags (eg. make / model / orientation / ICC profile), the image tags
+/// replace the ones with the same tag. `sub_ifds` are written after it with their pointer tags.
+pub(crate) fn write_image(
+    out: &mut impl Write,
+    width: u32,
+    height: u32,
+    colors: u16,
+    samples: Samples<'_>,
+    mut ifd0: Ifd,
+    sub_ifds: &[(u16, Ifd)],
+) -> std::io::Result<()> {
+    let invalid = |message| std::io::Error::new(std::io::ErrorKind::InvalidInput, message);
+    let (bits, len) = match samples {
+        Samples::U8(samples) => (8, samples.len()),
+        Samples::U16(samples) => (16, samples.len() * 2),
+    };
+    let photometric = match colors {
+        1 => 1,
+        3 => 2,
+        _ => return Err(invalid("only gray and rgb images can be written")),
+    };
+    if len != width as usize * height as usize * colors as usize * (bits / 8) {
+        return Err(invalid("sample count doesn't match the image size"));
+    }
+    let byte_count = u32::try_from(len).map_err(|_| invalid("image is too large for a tiff"))?;
+
+    ifd0.insert(IMAGE_WIDTH, Value::Long(vec![width]));
+    ifd0.insert(IMAGE_LENGTH, Value::Long(vec![height]));
+    ifd0.insert(
+        BITS_PER_SAMPLE,
+        Value::Short(vec![bits as u16; colors as usize]),
+    );
+    ifd0.insert(COMPRESSION, Value::Short(vec![1]));
+    ifd0.insert(PHOTOMETRIC_INTERPRETATION, Value::Short(vec![photometric]));
+    ifd0.insert(SAMPLES_PER_PIXEL, Value::Short(vec![colors]));
+    ifd0.insert(ROWS_PER_STRIP, Value::Long(vec![height.max(1)]));
+    ifd0.insert(STRIP_BYTE_COUNTS, Value::Long(vec![byte_count]));
+    ifd0.insert(X_RESOLUTION, Value::Rational(vec![(300, 1)]));
+    ifd0.insert(Y_RESOLUTION, Value::Rational(vec![(300, 1)]));
+    ifd0.insert(PLANAR_CONFIGURATION, Value::Short(vec![1]));
+    ifd0.insert(RESOLUTION_UNIT, Value::Short(vec![2]));
+    ifd0.insert(STRIP_OFFSETS, Value::Long(vec![0]));
+
+    let strip_offset = link_ifds(&mut ifd0, sub_ifds, HEADER.len());
+    let strip_offset =
+        u32::try_from(strip_offset).map_err(|_| invalid("metadata is too large for a tiff"))?;
+    if strip_offset.checked_add(byte_count).is_none() {
+        return Err(invalid("image is too large for a tiff"));
+    }
+    ifd0.insert(STRIP_OFFSETS, Value::Long(vec![strip_offset]));
+
+    let mut header = HEADER.to_vec();
+    ifd0.write(&mut header, 0);
+    for (_, ifd) in sub_ifds {
+        ifd.write(&mut header, 0);
+    }
+    out.write_all(&header)?;
+    samples.write_be(out)
+}
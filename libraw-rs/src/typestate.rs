@@ -0,0 +1,361 @@
+//! A typestate wrapper over [`Processor`]
+//!
+//! libraw expects its functions to be called in a specific order
+//! (`open` -> `unpack` -> `dcraw_process` -> `dcraw_make_mem_image`) and reports any deviation at
+//! runtime with [`InternalLibrawError::OutOfOrderCall`](crate::error::InternalLibrawError::OutOfOrderCall).
+//!
+//! [`TypedProcessor`] encodes the stage in its type so that only the calls which are valid for
+//! the current stage are available.
+//!
+//! ```no_run
+//! use libraw_r::typestate::TypedProcessor;
+//! # fn main() -> Result<(), libraw_r::LibrawError> {
+//! let processor = TypedProcessor::new();
+//! let processed = processor.open("image.nef")?.unpack()?.process()?;
+//! let image = processed.make_mem_image()?;
+//! // Go back to the empty state to reuse the libraw_data_t for another file
+//! let _processor = processed.recycle();
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! A failed transition returns a [`TransitionError`] which holds the processor in the stage it was
+//! in, so the call can be retried or the processor recycled.
+//!
+//! The untyped [`Processor`] is still available through [`TypedProcessor::into_untyped`] as an
+//! escape hatch.
+
+use core::marker::PhantomData;
+use core::ops::Deref;
+use std::path::Path;
+
+use crate::{LibrawError, ProcessedImage, Processor, ProcessorBuilder};
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Marker trait for the stages of a [`TypedProcessor`]
+pub trait State: sealed::Sealed {}
+
+/// Marker trait for the stages in which a file has been opened
+pub trait HasFile: State {}
+
+/// Marker trait for the stages in which the raw data has been unpacked
+pub trait HasRawData: HasFile {}
+
+/// Nothing has been opened yet (or the processor has been recycled)
+#[derive(Debug)]
+pub enum Empty {}
+/// A file has been opened and its metadata has been parsed
+#[derive(Debug)]
+pub enum Opened {}
+/// The raw data has been unpacked into memory
+#[derive(Debug)]
+pub enum Unpacked {}
+/// The raw data has been processed with `dcraw_process`
+#[derive(Debug)]
+pub enum Processed {}
+
+impl sealed::Sealed for Empty {}
+impl sealed::Sealed for Opened {}
+impl sealed::Sealed for Unpacked {}
+impl sealed::Sealed for Processed {}
+
+impl State for Empty {}
+impl State for Opened {}
+impl State for Unpacked {}
+impl State for Processed {}
+
+impl HasFile for Opened {}
+impl HasFile for Unpacked {}
+impl HasFile for Processed {}
+
+impl HasRawData for Unpacked {}
+impl HasRawData for Processed {}
+
+/// A [`Processor`] which tracks the stage of the libraw pipeline in its type
+///
+/// Derefs to the untyped [`Processor`] so all the read only accessors (`idata`, `sizes`, ...) are
+/// available in every stage.
+pub struct TypedProcessor<S: State = Empty> {
+    inner: Processor,
+    _state: PhantomData<S>,
+}
+
+impl<S: State> Deref for TypedProcessor<S> {
+    type Target = Processor;
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+/// The error of a failed transition along with the processor, still in the stage `S`
+pub struct TransitionError<S: State>(Box<TransitionErrorInner<S>>);
+
+/// Boxed to keep the `Result`s of the transitions small
+struct TransitionErrorInner<S: State> {
+    error: LibrawError,
+    processor: TypedProcessor<S>,
+}
+
+impl<S: State> TransitionError<S> {
+    pub fn error(&self) -> &LibrawError {
+        &self.0.error
+    }
+
+    pub fn processor(&self) -> &TypedProcessor<S> {
+        &self.0.processor
+    }
+
+    /// Get the processor back to retry or recycle it
+    pub fn into_processor(self) -> TypedProcessor<S> {
+        self.0.processor
+    }
+
+    pub fn into_parts(self) -> (LibrawError, TypedProcessor<S>) {
+        (self.0.error, self.0.processor)
+    }
+}
+
+impl<S: State> core::fmt::Debug for TransitionError<S> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("TransitionError")
+            .field("error", &self.0.error)
+            .field("state", &core::any::type_name::<S>())
+            .finish()
+    }
+}
+
+impl<S: State> core::fmt::Display for TransitionError<S> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.0.error.fmt(f)
+    }
+}
+
+impl<S: State> std::error::Error for TransitionError<S> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.0.error)
+    }
+}
+
+impl<S: State> From<TransitionError<S>> for LibrawError {
+    /// Drops the processor
+    fn from(error: TransitionError<S>) -> Self {
+        error.0.error
+    }
+}
+
+impl<S: State> TypedProcessor<S> {
+    fn transition<N: State>(self) -> TypedProcessor<N> {
+        TypedProcessor {
+            inner: self.inner,
+            _state: PhantomData,
+        }
+    }
+
+    /// Run `f` and go to the stage `N` if it succeeds
+    fn try_transition<N: State>(
+        mut self,
+        f: impl FnOnce(&mut Processor) -> Result<(), LibrawError>,
+    ) -> Result<TypedProcessor<N>, TransitionError<S>> {
+        match f(&mut self.inner) {
+            Ok(()) => Ok(self.transition()),
+            Err(error) => Err(TransitionError(Box::new(TransitionErrorInner {
+                error,
+                processor: self,
+            }))),
+        }
+    }
+
+    /// Calls libraw_recycle and goes back to the [`Empty`] state
+    pub fn recycle(mut self) -> TypedProcessor<Empty> {
+        // Processor::recycle never fails
+        let _ = self.inner.recycle();
+        self.transition()
+    }
+
+    /// Drops the typestate and returns the untyped processor
+    pub fn into_untyped(self) -> Processor {
+        self.inner
+    }
+
+    /// Same as [`Processor::set_progress_callback`]
+    pub fn set_progress_callback<T, F>(
+        &mut self,
+        callback: F,
+        data: T,
+    ) -> Result<crate::progress::ProgressMonitor<T>, LibrawError>
+    where
+        F: Fn(crate::progress::ProgressCallbackArgs<T>) -> i32 + Send + Sync + 'static,
+        T: Send + Sync,
+    {
+        self.inner.set_progress_callback(callback, data)
+    }
+}
+
+impl TypedProcessor<Empty> {
+    /// Calls libraw_init(0)
+    pub fn new() -> Self {
+        Self::from(Processor::default())
+    }
+
+    /// Calls libraw_open_file
+    pub fn open(
+        self,
+        path: impl AsRef<Path>,
+    ) -> Result<TypedProcessor<Opened>, TransitionError<Empty>> {
+        self.try_transition(|p| p.open(path))
+    }
+
+    /// Same as [`Processor::open_buffer`]
+    pub fn open_buffer<B>(self, buffer: B) -> Result<TypedProcessor<Opened>, TransitionError<Empty>>
+    where
+        B: AsRef<[u8]> + Send + 'static,
+    {
+        self.try_transition(|p| p.open_buffer(buffer))
+    }
+
+    /// Same as [`Processor::open_reader`]
+    pub fn open_reader<R>(self, reader: R) -> Result<TypedProcessor<Opened>, TransitionError<Empty>>
+    where
+        R: std::io::Read + std::io::Seek + Send + 'static,
+    {
+        self.try_transition(|p| p.open_reader(reader))
+    }
+
+    /// Same as [`Processor::open_mmap`]
+    ///
+    /// # Safety
+    /// See [`Processor::open_mmap`]
+    #[cfg(feature = "mmap")]
+    pub unsafe fn open_mmap(
+        self,
+        path: impl AsRef<Path>,
+    ) -> Result<TypedProcessor<Opened>, TransitionError<Empty>> {
+        self.try_transition(|p| p.open_mmap(path))
+    }
+}
+
+impl Default for TypedProcessor<Empty> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<Processor> for TypedProcessor<Empty> {
+    /// Recycles the processor since we can't know which stage it was left in
+    fn from(mut processor: Processor) -> Self {
+        let _ = processor.recycle();
+        Self {
+            inner: processor,
+            _state: PhantomData,
+        }
+    }
+}
+
+impl ProcessorBuilder {
+    /// Build a [`TypedProcessor`] in the [`Empty`] state
+    pub fn build_typed(self) -> TypedProcessor<Empty> {
+        TypedProcessor {
+            inner: self.build(),
+            _state: PhantomData,
+        }
+    }
+}
+
+impl<S: HasFile> TypedProcessor<S> {
+    /// Unpack the thumbnail for the file
+    ///
+    /// Can be called in any stage after the file has been opened
+    pub fn unpack_thumb(&mut self) -> Result<(), LibrawError> {
+        self.inner.unpack_thumb()
+    }
+
+    /// Unpack the thumbnail at `index` in the thumbs_list
+    pub fn unpack_thumb_ex(&mut self, index: libc::c_int) -> Result<(), LibrawError> {
+        self.inner.unpack_thumb_ex(index)
+    }
+
+    /// Calls libraw_dcraw_make_mem_thumb
+    ///
+    /// Unpacks the thumbnail first since libraw_dcraw_make_mem_thumb needs it
+    pub fn make_mem_thumb(&mut self) -> Result<ProcessedImage, LibrawError> {
+        self.inner.unpack_thumb()?;
+        self.inner.dcraw_process_make_mem_thumb()
+    }
+
+    /// Returns the embedded jpeg thumbnail with the orientation applied
+    #[cfg(feature = "jpeg")]
+    pub fn get_jpeg(&mut self) -> Result<Vec<u8>, LibrawError> {
+        self.inner.get_jpeg()
+    }
+}
+
+impl TypedProcessor<Opened> {
+    /// Adjusts sizes and changes the resolution according to the flip values
+    pub fn adjust_sizes_info_only(&mut self) -> Result<(), LibrawError> {
+        self.inner.adjust_sizes_info_only()
+    }
+
+    /// Unpack the raw data and read it to memory
+    pub fn unpack(self) -> Result<TypedProcessor<Unpacked>, TransitionError<Opened>> {
+        self.try_transition(Processor::unpack)
+    }
+}
+
+impl<S: HasRawData> TypedProcessor<S> {
+    /// Same as [`Processor::raw2image`]
+    pub fn raw2image(&mut self) -> Result<(), LibrawError> {
+        self.inner.raw2image()
+    }
+
+    /// Same as [`Processor::subtract_black`]
+    pub fn subtract_black(&mut self) -> Result<(), LibrawError> {
+        self.inner.subtract_black()
+    }
+
+    /// Same as [`Processor::image4_mut`]
+    pub fn image4_mut(&mut self) -> Result<&mut [[u16; 4]], LibrawError> {
+        self.inner.image4_mut()
+    }
+}
+
+impl TypedProcessor<Unpacked> {
+    /// Calls libraw_dcraw_process
+    pub fn process(self) -> Result<TypedProcessor<Processed>, TransitionError<Unpacked>> {
+        self.try_transition(Processor::dcraw_process)
+    }
+}
+
+impl TypedProcessor<Processed> {
+    /// Calls libraw_dcraw_make_mem_image
+    pub fn make_mem_image(&self) -> Result<ProcessedImage, LibrawError> {
+        let mut errc = 0;
+        let data = unsafe {
+            crate::sys::libraw_dcraw_make_mem_image(self.inner.inner.as_ptr(), &mut errc)
+        };
+        LibrawError::check(errc)?;
+        Ok(ProcessedImage {
+            inner: core::ptr::NonNull::new(data).ok_or_else(|| {
+                LibrawError::CustomError("libraw_dcraw_make_mem_image returned null".into())
+            })?,
+        })
+    }
+
+    /// Writes the processed image to a ppm / tiff file depending on `params().output_tiff`
+    pub fn dcraw_ppm_tiff_writer(&self, path: impl AsRef<Path>) -> Result<(), LibrawError> {
+        LibrawError::check(unsafe {
+            crate::sys::libraw_dcraw_ppm_tiff_writer(
+                self.inner.inner.as_ptr(),
+                crate::path_to_cstr(path)?.as_ptr(),
+            )
+        })
+    }
+
+    /// Writes the processed image as a ppm / tiff into `writer`, see
+    /// [`Processor::write_ppm_tiff`]
+    pub fn write_ppm_tiff<W: std::io::Write>(&mut self, writer: W) -> Result<(), LibrawError> {
+        self.inner.write_ppm_tiff(writer)
+    }
+}
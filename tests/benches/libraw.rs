@@ -1,7 +1,7 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 
 const IMAGE: &[u8] = include_bytes!("../assets/RAW_NIKON_D3X.NEF");
-fn post_process(data: &[u8]) -> Vec<u8> {
+fn post_process(data: &'static [u8]) -> Vec<u8> {
     use libraw_r::Processor;
     let mut processor = Processor::default();
     processor.open_buffer(data).unwrap();
@@ -11,13 +11,28 @@ fn post_process(data: &[u8]) -> Vec<u8> {
     i.as_slice_u8().into()
 }
 
-fn unpack(data: &[u8]) {
+fn unpack(data: &'static [u8]) {
     use libraw_r::Processor;
     let mut processor = Processor::default();
     processor.open_buffer(black_box(data)).unwrap();
     processor.unpack().unwrap();
 }
 
+fn unpacked() -> libraw_r::Processor {
+    let mut processor = libraw_r::Processor::default();
+    processor.open_buffer(IMAGE).unwrap();
+    processor.unpack().unwrap();
+    processor
+}
+
+fn render(processor: &mut libraw_r::Processor, half_size: bool) -> Vec<u8> {
+    let options = libraw_r::ProcessingOptions {
+        half_size,
+        ..Default::default()
+    };
+    processor.render(&options).unwrap().as_slice_u8().into()
+}
+
 fn libraw_benches(c: &mut Criterion) {
     let mut group = c.benchmark_group("Normal Operations");
     group
@@ -26,6 +41,14 @@ fn libraw_benches(c: &mut Criterion) {
         .bench_function("Unpack", |b| b.iter(|| unpack(black_box(IMAGE))))
         .bench_function("Post Processing", |b| {
             b.iter(|| post_process(black_box(IMAGE)))
+        })
+        .bench_function("Render Without Unpack", |b| {
+            let mut processor = unpacked();
+            b.iter(|| render(&mut processor, black_box(false)))
+        })
+        .bench_function("Render Half Size Without Unpack", |b| {
+            let mut processor = unpacked();
+            b.iter(|| render(&mut processor, black_box(true)))
         });
 
     group.finish();
@@ -0,0 +1,88 @@
+#[test]
+fn open_bayer_8bit() {
+    use libraw_r::bayer::*;
+    use libraw_r::raw::CfaPattern;
+    use libraw_r::*;
+    let spec = BayerSpec {
+        width: 64,
+        height: 48,
+        margins: BayerMargins::default(),
+        pattern: CfaPattern::Rggb,
+        bits: 8,
+        unused_bits: 0,
+        black_level: 0,
+        flags: BayerFlags::default(),
+    };
+    let frame = vec![128u8; spec.frame_bytes().unwrap()];
+    let mut p = Processor::default();
+    p.open_bayer(frame, spec)
+        .expect("Failed to open bayer frame");
+    p.unpack().expect("Failed to unpack");
+    assert_eq!(p.raw_image().unwrap().cfa_pattern(), Some(CfaPattern::Rggb));
+    p.dcraw_process().expect("Failed to process");
+}
+
+#[test]
+fn open_bayer_short_buffer() {
+    use libraw_r::bayer::*;
+    use libraw_r::raw::CfaPattern;
+    use libraw_r::*;
+    let spec = BayerSpec {
+        width: 64,
+        height: 48,
+        margins: BayerMargins::default(),
+        pattern: CfaPattern::Rggb,
+        bits: 16,
+        unused_bits: 0,
+        black_level: 0,
+        flags: BayerFlags::default(),
+    };
+    let frame = vec![0u8; spec.frame_bytes().unwrap() - 1];
+    let mut p = Processor::default();
+    assert!(matches!(
+        p.open_bayer(frame, spec),
+        Err(LibrawError::InvalidBayerSpec(_))
+    ));
+}
+
+#[test]
+fn open_bayer_invalid_spec() {
+    use libraw_r::bayer::*;
+    use libraw_r::raw::CfaPattern;
+    use libraw_r::*;
+    let spec = BayerSpec {
+        width: 60,
+        height: 48,
+        margins: BayerMargins::default(),
+        pattern: CfaPattern::Rggb,
+        bits: 10,
+        unused_bits: 0,
+        black_level: 0,
+        flags: BayerFlags::default(),
+    };
+    let mut p = Processor::default();
+    let frame = vec![0u8; spec.frame_bytes().unwrap()];
+    p.open_bayer(frame, spec)
+        .expect("Failed to open loose 10 bit frame");
+
+    // A loose 10 bit row of 64 pixels is padded to 11 bits per pixel
+    let padded = BayerSpec { width: 64, ..spec };
+    let frame = vec![0u8; padded.frame_bytes().unwrap()];
+    assert!(matches!(
+        p.open_bayer(frame, padded),
+        Err(LibrawError::InvalidBayerSpec(_))
+    ));
+
+    let spec = BayerSpec {
+        flags: BayerFlags {
+            flip: Flip(8),
+            ..Default::default()
+        },
+        ..spec
+    };
+    let frame = vec![0u8; spec.frame_bytes().unwrap()];
+    assert!(matches!(
+        p.open_bayer(frame, spec),
+        Err(LibrawError::InvalidBayerSpec(_))
+    ));
+}
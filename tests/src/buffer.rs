@@ -0,0 +1,31 @@
+#[test]
+fn open_owned_buffer() {
+    use libraw_r::*;
+    let mut p = Processor::default();
+    {
+        let buffer = std::fs::read(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/assets/RAW_NIKON_D3X.NEF"
+        ))
+        .expect("Failed to read file");
+        p.open_buffer(buffer).expect("Failed to open buffer");
+    }
+    // The processor owns the buffer so unpacking after the original binding is gone is fine
+    p.unpack().expect("Failed to unpack");
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn open_mmap() {
+    use libraw_r::typestate::TypedProcessor;
+    use libraw_r::*;
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/RAW_NIKON_D3X.NEF");
+    let mut p = Processor::default();
+    // The asset isn't modified while the tests run
+    unsafe { p.open_mmap(path) }.expect("Failed to open mmap");
+    p.unpack().expect("Failed to unpack");
+    assert!(p.raw_image().is_ok());
+
+    let typed = unsafe { TypedProcessor::new().open_mmap(path) }.expect("Failed to open mmap");
+    typed.unpack().expect("Failed to unpack");
+}
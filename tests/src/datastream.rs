@@ -0,0 +1,14 @@
+#[test]
+fn open_reader() {
+    use libraw_r::*;
+    let file = std::fs::File::open(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/assets/RAW_NIKON_D3X.NEF"
+    ))
+    .expect("Failed to open file");
+    let mut p = Processor::default();
+    p.open_reader(std::io::BufReader::new(file))
+        .expect("Failed to open reader");
+    p.unpack().expect("Failed to unpack");
+    p.dcraw_process().expect("Failed to process");
+}
@@ -0,0 +1,144 @@
+#[cfg(feature = "tiff")]
+#[test]
+fn encode_16_bit_tiff() {
+    use libraw_r::options::*;
+    use libraw_r::*;
+    let mut p = Processor::default();
+    p.open(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/assets/RAW_NIKON_D3X.NEF"
+    ))
+    .expect("Failed to open file");
+    p.set_processing_options(&ProcessingOptions {
+        half_size: true,
+        output_bps: BitDepth::Sixteen,
+        ..Default::default()
+    })
+    .expect("Failed to set options");
+    let tiff = p
+        .encode(
+            OutputFormat::Tiff,
+            &EncodeOptions {
+                icc_profile: Some(icc::IccProfile::Custom(vec![0; 128])),
+                ..Default::default()
+            },
+        )
+        .expect("Failed to encode");
+    assert_eq!(tiff[..4], *b"MM\0\x2a");
+    let format = p.output_format();
+    assert_eq!(format.bits, 16);
+    let samples = format.width as usize * format.height as usize * 3 * 2;
+    assert!(tiff.len() > samples);
+}
+
+#[cfg(feature = "png")]
+#[test]
+fn encode_png() {
+    use libraw_r::options::*;
+    use libraw_r::*;
+    let mut p = Processor::default();
+    p.open(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/assets/RAW_NIKON_D3X.NEF"
+    ))
+    .expect("Failed to open file");
+    p.set_processing_options(&ProcessingOptions {
+        half_size: true,
+        ..Default::default()
+    })
+    .expect("Failed to set options");
+    let png = p
+        .encode(
+            OutputFormat::Png,
+            &EncodeOptions {
+                bit_depth: Some(BitDepth::Sixteen),
+                ..Default::default()
+            },
+        )
+        .expect("Failed to encode");
+    let image = image::load_from_memory_with_format(&png, image::ImageFormat::Png)
+        .expect("Failed to decode");
+    assert_eq!(image.color(), image::ColorType::Rgb16);
+    let format = p.output_format();
+    assert_eq!(
+        (image.width(), image.height()),
+        (format.width, format.height)
+    );
+    assert!(png.windows(4).any(|chunk| chunk == b"eXIf"));
+}
+
+#[cfg(any(feature = "jpeg", feature = "png", feature = "tiff", feature = "webp"))]
+#[test]
+fn encode_options_validation() {
+    use libraw_r::*;
+    let options = EncodeOptions {
+        quality: 0,
+        ..Default::default()
+    };
+    assert!(matches!(
+        options.validate(),
+        Err(LibrawError::InvalidOption(_))
+    ));
+    assert!(EncodeOptions::default().validate().is_ok());
+}
+
+#[cfg(feature = "avif")]
+#[test]
+fn encode_avif_icc_profile() {
+    use libraw_r::options::*;
+    use libraw_r::*;
+    let mut p = Processor::default();
+    p.open(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/assets/RAW_NIKON_D3X.NEF"
+    ))
+    .expect("Failed to open file");
+    p.set_processing_options(&ProcessingOptions {
+        half_size: true,
+        ..Default::default()
+    })
+    .expect("Failed to set options");
+    let avif = p
+        .encode(OutputFormat::Avif, &Default::default())
+        .expect("Failed to encode");
+    assert_eq!(avif[4..8], *b"ftyp");
+    p.encode(
+        OutputFormat::Avif,
+        &EncodeOptions {
+            icc_profile: Some(icc::IccProfile::Auto),
+            ..Default::default()
+        },
+    )
+    .expect("Failed to encode");
+
+    // Other color spaces can't be tagged
+    assert!(matches!(
+        p.encode(
+            OutputFormat::Avif,
+            &EncodeOptions {
+                icc_profile: Some(icc::IccProfile::Custom(vec![0; 128])),
+                ..Default::default()
+            },
+        ),
+        Err(LibrawError::InvalidOption(_))
+    ));
+    p.set_processing_options(&ProcessingOptions {
+        half_size: true,
+        output_color: OutputColorSpace::AdobeRgb,
+        ..Default::default()
+    })
+    .expect("Failed to set options");
+    assert!(matches!(
+        p.encode(OutputFormat::Avif, &Default::default()),
+        Err(LibrawError::InvalidOption(_))
+    ));
+    // Unless the profile is left out on purpose
+    p.encode(
+        OutputFormat::Avif,
+        &EncodeOptions {
+            icc_profile: Some(icc::IccProfile::None),
+            ..Default::default()
+        },
+    )
+    .expect("Failed to encode");
+}
@@ -0,0 +1,48 @@
+#[test]
+fn exif_from_metadata() {
+    use libraw_r::*;
+    let mut p = Processor::default();
+    p.open(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/assets/RAW_NIKON_D3X.NEF"
+    ))
+    .expect("Failed to open file");
+    let exif = p.exif_builder().build();
+    assert!(exif.starts_with(b"MM\0\x2a\0\0\0\x08"));
+
+    let u16_at = |at: usize| u16::from_be_bytes([exif[at], exif[at + 1]]);
+    let u32_at = |at: usize| u32::from_be_bytes(exif[at..at + 4].try_into().unwrap()) as usize;
+    // The value of `tag` in the IFD at `ifd`, without the NUL of ascii values
+    let value = |ifd: usize, tag: u16| -> Option<&[u8]> {
+        let entry = (0..u16_at(ifd) as usize)
+            .map(|i| ifd + 2 + i * 12)
+            .find(|&entry| u16_at(entry) == tag)?;
+        let kind = u16_at(entry + 2);
+        let size = match kind {
+            3 => 2,
+            4 => 4,
+            _ => 1,
+        } * u32_at(entry + 4);
+        let offset = if size <= 4 {
+            entry + 8
+        } else {
+            u32_at(entry + 8)
+        };
+        let value = &exif[offset..offset + size];
+        match kind {
+            2 => value.strip_suffix(b"\0"),
+            _ => Some(value),
+        }
+    };
+    let metadata = p.metadata();
+    assert_eq!(value(8, 0x010f), Some(metadata.camera.make.as_bytes()));
+    assert_eq!(value(8, 0x0110), Some(&b"D3X"[..]));
+
+    // DateTime / DateTimeOriginal in UTC with the offset
+    let date_time = value(8, 0x0132).expect("Missing DateTime");
+    assert_eq!(date_time.len(), 19);
+    let pointer = value(8, 0x8769).expect("Missing EXIF IFD");
+    let exif_ifd = u32::from_be_bytes(pointer.try_into().unwrap()) as usize;
+    assert_eq!(value(exif_ifd, 0x9003), Some(date_time));
+    assert_eq!(value(exif_ifd, 0x9011), Some(&b"+00:00"[..]));
+}
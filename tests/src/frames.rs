@@ -0,0 +1,39 @@
+#[test]
+fn single_frame() {
+    use libraw_r::*;
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/RAW_NIKON_D3X.NEF");
+    let mut p = Processor::default();
+    p.open(path).expect("Failed to open file");
+    assert_eq!(p.frame_count(), 1);
+
+    let frames = p
+        .frames()
+        .collect::<Result<Vec<_>, _>>()
+        .expect("Failed to process frames");
+    assert_eq!(frames.len(), 1);
+    assert!(frames[0].width() > 0);
+
+    let mut count = 0;
+    p.for_each_raw_frame(|frame, raw| {
+        assert_eq!(frame, count);
+        assert!(raw.width() > 0);
+        count += 1;
+        Ok(())
+    })
+    .expect("Failed to iterate raw frames");
+    assert_eq!(count, 1);
+
+    assert!(p.unpack_frame(1).is_err());
+    // The frame selected before is used for the next open
+    assert_eq!(p.selected_frame(), 0);
+    p.unpack_frame(0).expect("Failed to unpack frame");
+
+    // Buffers are reopened from the buffer kept by the processor
+    let mut p = Processor::default();
+    assert!(p.reopen().is_err());
+    p.open_buffer(std::fs::read(path).expect("Failed to read file"))
+        .expect("Failed to open buffer");
+    assert_eq!(p.frames().count(), 1);
+    p.recycle().expect("Failed to recycle");
+    assert!(p.unpack_frame(0).is_err());
+}
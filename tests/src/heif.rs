@@ -0,0 +1,90 @@
+#[test]
+fn wrap_hevc() {
+    // Add the emulation prevention bytes of an H265 NAL unit
+    fn escape(rbsp: &[u8]) -> Vec<u8> {
+        let mut nal = Vec::new();
+        for &b in rbsp {
+            if nal.ends_with(&[0, 0]) && b <= 3 {
+                nal.push(3);
+            }
+            nal.push(b);
+        }
+        nal
+    }
+    let be = |data: &[u8], at: usize| u32::from_be_bytes(data[at..at + 4].try_into().unwrap());
+
+    // 64x48 main profile SPS as (value, bits), None for unsigned exp-golomb
+    let fields: [(u64, Option<u32>); 15] = [
+        // vps_id, max_sub_layers_minus1, temporal_id_nesting
+        (0, Some(4)),
+        (0, Some(3)),
+        (1, Some(1)),
+        // general_profile_space, tier, profile_idc, compatibility and constraint flags, level
+        (0, Some(2)),
+        (0, Some(1)),
+        (1, Some(5)),
+        (0x6000_0000, Some(32)),
+        (0x9000_0000_0000, Some(48)),
+        (93, Some(8)),
+        // sps_id, chroma_format_idc, width, height
+        (0, None),
+        (1, None),
+        (64, None),
+        (48, None),
+        // conformance_window_flag, then bit_depth_luma_minus8 = bit_depth_chroma_minus8 = 0
+        // and the rbsp stop bit
+        (0, Some(1)),
+        (0b111, Some(3)),
+    ];
+    let mut bits = Vec::new();
+    for (value, count) in fields {
+        let (value, count) = match count {
+            Some(count) => (value, count),
+            None => (value + 1, 2 * (64 - (value + 1).leading_zeros()) - 1),
+        };
+        bits.extend((0..count).rev().map(|i| (value >> i) & 1 == 1));
+    }
+    let mut sps = vec![0x42, 0x01];
+    sps.extend(bits.chunks(8).map(|byte| {
+        (0..8).fold(0, |acc, i| {
+            acc << 1 | byte.get(i).copied().unwrap_or(false) as u8
+        })
+    }));
+    let sps = escape(&sps);
+    let vps = [0x40, 0x01, 0x0c, 0x01];
+    let pps = [0x44, 0x01, 0xc1];
+    let slice = [0x26, 0x01, 0xaf, 0x10, 0x20];
+
+    let mut bitstream = vec![0, 0, 0, 1];
+    bitstream.extend_from_slice(&vps);
+    for nal in [&sps[..], &pps, &slice] {
+        bitstream.extend_from_slice(&[0, 0, 1]);
+        bitstream.extend_from_slice(nal);
+    }
+    let heif = libraw_r::heif::wrap_hevc(&bitstream, 64, 48).expect("Failed to wrap");
+
+    assert_eq!(heif[4..12], *b"ftypheic");
+    let meta = be(&heif, 0) as usize;
+    assert_eq!(heif[meta + 4..meta + 8], *b"meta");
+    let iloc = heif
+        .windows(4)
+        .position(|w| w == b"iloc")
+        .expect("Missing iloc");
+    let offset = be(&heif, iloc + 18) as usize;
+    let length = be(&heif, iloc + 22) as usize;
+    assert_eq!(offset, meta + be(&heif, meta) as usize + 8);
+    assert_eq!(heif[offset - 4..offset], *b"mdat");
+    assert_eq!(be(&heif, offset - 8) as usize, length + 8);
+    assert_eq!(offset + length, heif.len());
+
+    // Only the slice goes into mdat, length prefixed
+    assert_eq!(be(&heif, offset) as usize, slice.len());
+    assert_eq!(heif[offset + 4..], slice);
+
+    let ispe = heif
+        .windows(4)
+        .position(|w| w == b"ispe")
+        .expect("Missing ispe");
+    assert_eq!((be(&heif, ispe + 8), be(&heif, ispe + 12)), (64, 48));
+    assert!(heif.windows(4).any(|w| w == b"hvcC"));
+}
@@ -0,0 +1,141 @@
+#[test]
+fn output_profiles() {
+    use libraw_r::icc::output_profile;
+    use libraw_r::options::*;
+    /// (offset, size) of `signature` in the tag table of `profile`
+    fn tag(profile: &[u8], signature: &[u8; 4]) -> Option<(usize, usize)> {
+        let be = |at: usize| u32::from_be_bytes(profile[at..at + 4].try_into().unwrap()) as usize;
+        (0..be(128))
+            .map(|i| 132 + i * 12)
+            .find(|&entry| &profile[entry..entry + 4] == signature)
+            .map(|entry| (be(entry + 4), be(entry + 8)))
+    }
+
+    assert_eq!(output_profile(OutputColorSpace::Raw, Gamma::Bt709), None);
+    for space in [
+        OutputColorSpace::Srgb,
+        OutputColorSpace::AdobeRgb,
+        OutputColorSpace::WideGamut,
+        OutputColorSpace::ProPhoto,
+        OutputColorSpace::Xyz,
+        OutputColorSpace::Aces,
+    ] {
+        for gamma in [Gamma::Bt709, Gamma::Srgb, Gamma::Linear] {
+            let profile = output_profile(space, gamma).expect("Missing profile");
+            assert_eq!(
+                u32::from_be_bytes(profile[..4].try_into().unwrap()) as usize,
+                profile.len()
+            );
+            assert_eq!(profile[36..40], *b"acsp");
+            assert_eq!(profile[12..24], *b"mntrRGB XYZ ");
+            for signature in [b"desc", b"wtpt", b"rXYZ", b"gTRC"] {
+                let (offset, size) = tag(&profile, signature).expect("Missing tag");
+                assert_eq!(offset % 4, 0);
+                assert!(offset + size <= profile.len());
+            }
+        }
+    }
+
+    // The red colorant of sRGB adapted to D50
+    let profile = output_profile(OutputColorSpace::Srgb, Gamma::Srgb).unwrap();
+    let (offset, _) = tag(&profile, b"rXYZ").unwrap();
+    let xyz: Vec<f64> = profile[offset + 8..offset + 20]
+        .chunks(4)
+        .map(|v| i32::from_be_bytes(v.try_into().unwrap()) as f64 / 65536.0)
+        .collect();
+    for (value, expected) in xyz.iter().zip([0.4361, 0.2225, 0.0139]) {
+        assert!((value - expected).abs() < 1e-3, "{:?}", xyz);
+    }
+    // A linear curve has no entries
+    let linear = output_profile(OutputColorSpace::Srgb, Gamma::Linear).unwrap();
+    assert_eq!(tag(&linear, b"rTRC").unwrap().1, 12);
+}
+
+#[test]
+fn auto_profile_without_lcms() {
+    use libraw_r::icc::{output_profile, IccProfile};
+    use libraw_r::options::*;
+    use libraw_r::*;
+    let mut p = Processor::default();
+    // libraw is built without LCMS so it can't convert to a profile file
+    assert!(p
+        .set_processing_options(&ProcessingOptions {
+            output_profile: Some("/nonexistent/profile.icc".into()),
+            ..Default::default()
+        })
+        .is_err());
+    let options = ProcessingOptions {
+        output_color: OutputColorSpace::AdobeRgb,
+        ..Default::default()
+    };
+    p.set_processing_options(&options)
+        .expect("Failed to set options");
+    assert_eq!(
+        p.output_icc_profile().expect("Failed to resolve"),
+        output_profile(OutputColorSpace::AdobeRgb, options.gamma)
+    );
+    assert_eq!(
+        p.resolve_icc_profile(&IccProfile::None)
+            .expect("Failed to resolve"),
+        None
+    );
+}
+
+#[cfg(feature = "jpeg")]
+#[test]
+fn jpeg_icc_profile() {
+    use libraw_r::icc::IccProfile;
+    use libraw_r::*;
+    let mut p = Processor::default();
+    p.open(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/assets/RAW_NIKON_D3X.NEF"
+    ))
+    .expect("Failed to open file");
+    p.params().half_size = 1;
+    let jpeg = p.to_jpeg(80).expect("Failed to encode");
+    assert!(jpeg.windows(11).any(|w| w == b"ICC_PROFILE"));
+
+    p.set_icc_profile(IccProfile::None);
+    let jpeg = p.to_jpeg(80).expect("Failed to encode");
+    assert!(!jpeg.windows(11).any(|w| w == b"ICC_PROFILE"));
+}
+
+#[cfg(feature = "png")]
+#[test]
+fn png_icc_profile() {
+    use libraw_r::icc::{output_profile, IccProfile};
+    use libraw_r::options::*;
+    use libraw_r::*;
+    let mut p = Processor::default();
+    p.open(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/assets/RAW_NIKON_D3X.NEF"
+    ))
+    .expect("Failed to open file");
+    p.set_processing_options(&ProcessingOptions {
+        half_size: true,
+        output_color: OutputColorSpace::AdobeRgb,
+        ..Default::default()
+    })
+    .expect("Failed to set options");
+    assert_eq!(
+        p.output_icc_profile().expect("Failed to resolve"),
+        output_profile(OutputColorSpace::AdobeRgb, Gamma::Bt709)
+    );
+    let png = p
+        .encode(OutputFormat::Png, &Default::default())
+        .expect("Failed to encode");
+    assert!(png.windows(4).any(|chunk| chunk == b"iCCP"));
+
+    let png = p
+        .encode(
+            OutputFormat::Png,
+            &EncodeOptions {
+                icc_profile: Some(IccProfile::None),
+                ..Default::default()
+            },
+        )
+        .expect("Failed to encode");
+    assert!(!png.windows(4).any(|chunk| chunk == b"iCCP"));
+}
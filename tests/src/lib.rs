@@ -1,2 +1,21 @@
+mod bayer;
+mod buffer;
+mod datastream;
+mod encode;
 mod exif;
+mod exif_builder;
+mod frames;
+mod heif;
+mod icc;
+mod metadata;
+mod options;
+mod orientation;
+mod ppm_tiff;
+mod presets;
 mod progress;
+mod raw;
+mod raw_options;
+mod render;
+mod resize;
+mod thumbnail;
+mod typestate;
@@ -0,0 +1,18 @@
+#[test]
+fn metadata_snapshot() {
+    use libraw_r::*;
+    let mut p = Processor::default();
+    p.open(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/assets/RAW_NIKON_D3X.NEF"
+    ))
+    .expect("Failed to open file");
+    let metadata = p.metadata();
+    assert_eq!(metadata.camera.make, "Nikon");
+    assert_eq!(metadata.camera.model, "D3X");
+    let iso = metadata.capture.iso.expect("Missing iso");
+    assert!((50.0..=25600.0).contains(&iso));
+    let shutter = metadata.capture.shutter.expect("Missing shutter");
+    assert!(shutter > std::time::Duration::ZERO && shutter.as_secs() < 60);
+    assert_eq!(metadata.sizes.width, p.sizes().width);
+}
@@ -0,0 +1,87 @@
+#[test]
+fn options_round_trip() {
+    use libraw_r::options::*;
+    use libraw_r::*;
+    let mut p = Processor::default();
+    assert_eq!(p.processing_options(), ProcessingOptions::default());
+
+    let options = ProcessingOptions {
+        demosaic: Demosaic::Dht,
+        highlight: HighlightMode::Rebuild(5),
+        white_balance: WhiteBalance::AutoRegion {
+            x: 10,
+            y: 20,
+            width: 100,
+            height: 50,
+        },
+        output_color: OutputColorSpace::ProPhoto,
+        output_bps: BitDepth::Sixteen,
+        gamma: Gamma::Srgb,
+        user_flip: Some(Flip::CW90),
+        user_cblack: [Some(10), None, None, Some(12)],
+        user_saturation: Some(0),
+        exposure: Some(ExposureCorrection {
+            shift: 2.0,
+            preserve_highlights: 0.5,
+        }),
+        bad_pixels: Some("/tmp/bad pixels.txt".into()),
+        ..Default::default()
+    };
+    p.set_processing_options(&options)
+        .expect("Failed to set options");
+    assert_eq!(p.processing_options(), options);
+    assert_eq!(p.params().user_qual, 11);
+    assert_eq!(p.params().output_color, 4);
+}
+
+#[test]
+fn options_validation() {
+    use libraw_r::options::*;
+    use libraw_r::*;
+    let invalid = [
+        ProcessingOptions {
+            highlight: HighlightMode::Rebuild(12),
+            ..Default::default()
+        },
+        ProcessingOptions {
+            white_balance: WhiteBalance::Custom([0.0; 4]),
+            ..Default::default()
+        },
+        ProcessingOptions {
+            gamma: Gamma::Custom {
+                power: 0.0,
+                slope: 1.0,
+            },
+            ..Default::default()
+        },
+        // libraw-sys doesn't build libraw with LCMS
+        ProcessingOptions {
+            camera_profile: Some(CameraProfile::Embedded),
+            ..Default::default()
+        },
+        ProcessingOptions {
+            output_profile: Some("/tmp/output.icc".into()),
+            ..Default::default()
+        },
+        ProcessingOptions {
+            exposure: Some(ExposureCorrection {
+                shift: 16.0,
+                preserve_highlights: 0.0,
+            }),
+            ..Default::default()
+        },
+    ];
+    let mut p = Processor::default();
+    for options in &invalid {
+        assert!(matches!(
+            options.validate(),
+            Err(LibrawError::InvalidOption(_))
+        ));
+        assert!(p.set_processing_options(options).is_err());
+    }
+    // Nothing reached libraw
+    assert_eq!(p.processing_options(), ProcessingOptions::default());
+
+    // The builder closes its processor when it is dropped on the error
+    assert!(Processor::builder().with_options(&invalid[0]).is_err());
+}
@@ -0,0 +1,112 @@
+/// A jpeg with an EXIF segment containing IFD0 with the given entries and an XMP segment
+#[cfg(all(test, feature = "jpeg"))]
+fn jpeg_with_exif(entries: &[[u8; 12]]) -> Vec<u8> {
+    let mut tiff = vec![b'M', b'M', 0, 42, 0, 0, 0, 8];
+    tiff.extend_from_slice(&(entries.len() as u16).to_be_bytes());
+    for entry in entries {
+        tiff.extend_from_slice(entry);
+    }
+    tiff.extend_from_slice(&[0; 4]);
+    let mut jpeg = vec![0xff, 0xd8, 0xff, 0xe1];
+    jpeg.extend_from_slice(&(tiff.len() as u16 + 8).to_be_bytes());
+    jpeg.extend_from_slice(b"Exif\0\0");
+    jpeg.extend_from_slice(&tiff);
+    let xmp = b"http://ns.adobe.com/xap/1.0/\0<rdf:Description tiff:Orientation=\"1\"/>";
+    jpeg.extend_from_slice(&[0xff, 0xe1]);
+    jpeg.extend_from_slice(&(xmp.len() as u16 + 2).to_be_bytes());
+    jpeg.extend_from_slice(xmp);
+    // A scan without components followed by EOI
+    jpeg.extend_from_slice(&[0xff, 0xda, 0, 3, 0, 0, 0xff, 0xd9]);
+    jpeg
+}
+
+#[cfg(feature = "jpeg")]
+#[test]
+fn add_to_keeps_exif() {
+    use libraw_r::Orientation;
+    // Make = "NIK" is stored inline, XResolution points to nowhere but must be kept as-is
+    let make = [0x01, 0x0f, 0, 2, 0, 0, 0, 4, b'N', b'I', b'K', 0];
+    let resolution = [0x01, 0x1a, 0, 5, 0, 0, 0, 1, 0, 0, 0x01, 0x00];
+    let jpeg = jpeg_with_exif(&[make, resolution]);
+
+    let jpeg = Orientation::CW90
+        .add_to(jpeg)
+        .expect("Failed to add orientation");
+    let exif = &jpeg[12..];
+    // IFD0 is moved to the end of the structure with the orientation inserted in tag order
+    let ifd = u32::from_be_bytes([exif[4], exif[5], exif[6], exif[7]]) as usize;
+    assert_eq!(&exif[ifd..ifd + 2], &[0, 3]);
+    assert_eq!(&exif[ifd + 2..ifd + 14], &make);
+    assert_eq!(
+        &exif[ifd + 14..ifd + 26],
+        &[0x01, 0x12, 0, 3, 0, 0, 0, 1, 0, 6, 0, 0]
+    );
+    assert_eq!(&exif[ifd + 26..ifd + 38], &resolution);
+
+    // Updating an existing tag doesn't move anything
+    let again = Orientation::CW180
+        .add_to(jpeg.clone())
+        .expect("Failed to add orientation");
+    assert_eq!(jpeg.len(), again.len());
+    assert_eq!(
+        &again[12 + ifd + 14..12 + ifd + 26],
+        &[0x01, 0x12, 0, 3, 0, 0, 0, 1, 0, 3, 0, 0]
+    );
+
+    let has = |jpeg: &[u8], needle: &[u8]| jpeg.windows(needle.len()).any(|w| w == needle);
+    assert!(has(&again, b"tiff:Orientation=\"3\""));
+
+    let stripped = Orientation::CW90
+        .add_to_stripped(jpeg)
+        .expect("Failed to add orientation");
+    assert!(!has(&stripped, b"NIK"));
+    assert!(!has(&stripped, b"tiff:Orientation"));
+
+    // 0 and 9 aren't valid EXIF orientations
+    for invalid in [Orientation(0), Orientation(9)] {
+        assert!(invalid.add_to(jpeg_with_exif(&[make])).is_err());
+    }
+}
+
+#[test]
+fn orientation_model() {
+    use libraw_r::{Flip, Orientation};
+    for o in Orientation::ALL {
+        assert_eq!(Orientation::from(Flip::from(o)), o);
+        assert_eq!(o.then(o.inverse()), Orientation::NONE);
+        assert_eq!(o.inverse().then(o), Orientation::NONE);
+    }
+    assert_eq!(
+        Orientation::CW90.then(Orientation::CW90),
+        Orientation::CW180
+    );
+    assert_eq!(Orientation::CW90.inverse(), Orientation::CCW90);
+    // Mirroring and then transposing rotates counter clockwise
+    assert_eq!(
+        Orientation::MIRROR_HORIZONTAL.then(Orientation::TRANSPOSE),
+        Orientation::CCW90
+    );
+    assert_eq!(Orientation::from_flip(Flip(8)), None);
+}
+
+#[test]
+fn apply_orientation() {
+    use libraw_r::Orientation;
+    // 3 x 2
+    // a b c
+    // d e f
+    let pixels = [b'a', b'b', b'c', b'd', b'e', b'f'];
+    let apply = |o: Orientation| o.apply(&pixels, 3, 2, 1).expect("Failed to apply");
+    assert_eq!(apply(Orientation::NONE), b"abcdef");
+    assert_eq!(apply(Orientation::MIRROR_HORIZONTAL), b"cbafed");
+    assert_eq!(apply(Orientation::CW180), b"fedcba");
+    assert_eq!(apply(Orientation::MIRROR_VERTICAL), b"defabc");
+    assert_eq!(apply(Orientation::TRANSPOSE), b"adbecf");
+    assert_eq!(apply(Orientation::CW90), b"daebfc");
+    assert_eq!(apply(Orientation::TRANSVERSE), b"fcebda");
+    assert_eq!(apply(Orientation::CW270), b"cfbead");
+    assert!(matches!(
+        Orientation::CW90.apply(&pixels, 3, 3, 1),
+        Err(libraw_r::LibrawError::InvalidImageLayout(_))
+    ));
+}
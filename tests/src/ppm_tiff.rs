@@ -0,0 +1,34 @@
+#[test]
+fn write_ppm_tiff_to_buffer() {
+    use libraw_r::options::*;
+    use libraw_r::*;
+    let mut p = Processor::default();
+    p.open(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/assets/RAW_NIKON_D3X.NEF"
+    ))
+    .expect("Failed to open file");
+    let mut options = ProcessingOptions {
+        half_size: true,
+        ..Default::default()
+    };
+    let image = p.render(&options).expect("Failed to render");
+    let mut ppm = Vec::new();
+    p.write_ppm_tiff_image(&image, &mut ppm)
+        .expect("Failed to write ppm");
+    let header = format!("P6\n{} {}\n255\n", image.width(), image.height());
+    assert!(ppm.starts_with(header.as_bytes()));
+    assert_eq!(ppm[header.len()..], *image.as_slice_u8());
+
+    // The processor stays usable
+    options.output_tiff = true;
+    options.output_bps = BitDepth::Sixteen;
+    p.set_processing_options(&options)
+        .expect("Failed to set options");
+    p.dcraw_process().expect("Failed to process");
+    let mut tiff = Vec::new();
+    p.write_ppm_tiff(&mut tiff).expect("Failed to write tiff");
+    assert_eq!(tiff[..4], *b"MM\0\x2a");
+    let samples = image.width() as usize * image.height() as usize * 3 * 2;
+    assert!(tiff.len() > samples);
+}
@@ -0,0 +1,96 @@
+#[test]
+fn builtin_presets() {
+    use libraw_r::options::WhiteBalance;
+    use libraw_r::presets::*;
+    let library = PresetLibrary::builtin();
+    library.validate().expect("Invalid builtin presets");
+    let preset = library
+        .get("half_size_auto_camera_wb")
+        .expect("Missing preset");
+    assert!(preset.options.half_size);
+    assert_eq!(preset.options.white_balance, WhiteBalance::CameraOrAuto);
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn presets_from_toml() {
+    use libraw_r::options::*;
+    use libraw_r::presets::*;
+    use libraw_r::*;
+    let library = PresetLibrary::from_toml_str(
+        r#"
+        default = "neutral"
+
+        [[preset]]
+        name = "neutral"
+
+        [[preset]]
+        name = "xtrans"
+        [preset.options]
+        demosaic = "ahd"
+
+        [[preset]]
+        name = "nikon"
+        [preset.options]
+        half_size = true
+        highlight = { rebuild = 5 }
+        white_balance = "camera"
+
+        [[rule]]
+        make = "fuji*"
+        xtrans = true
+        preset = "xtrans"
+
+        [[rule]]
+        make = "NIKON*"
+        model = "d3x"
+        preset = "nikon"
+
+        [[rule]]
+        make = "nikon"
+        model = "d3*"
+        xtrans = false
+        preset = "nikon"
+        "#,
+    )
+    .expect("Failed to parse presets");
+    assert_eq!(library.default.as_deref(), Some("neutral"));
+
+    let mut p = Processor::default();
+    p.open(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/assets/RAW_NIKON_D3X.NEF"
+    ))
+    .expect("Failed to open file");
+    let preset = p
+        .apply_preset(&library)
+        .expect("Failed to apply preset")
+        .expect("No preset selected");
+    assert_eq!(preset.name, "nikon");
+    let options = p.processing_options();
+    assert!(options.half_size);
+    assert_eq!(options.highlight, HighlightMode::Rebuild(5));
+    assert_eq!(options.white_balance, WhiteBalance::Camera);
+
+    assert!(matches!(
+        PresetLibrary::from_toml_str("[[rule]]\npreset = \"missing\""),
+        Err(LibrawError::InvalidPreset(_))
+    ));
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn presets_json_round_trip() {
+    use libraw_r::presets::*;
+    let mut library = PresetLibrary::builtin();
+    library.rules.push(PresetRule {
+        lens: Some("*50mm*".into()),
+        preset: "half_size".into(),
+        ..Default::default()
+    });
+    let json = library.to_json_string().expect("Failed to serialize");
+    assert_eq!(
+        PresetLibrary::from_json_str(&json).expect("Failed to parse"),
+        library
+    );
+}
@@ -0,0 +1,106 @@
+#[test]
+fn raw_image_cfa() {
+    use libraw_r::raw::CfaPattern;
+    use libraw_r::*;
+    let mut p = Processor::default();
+    p.open(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/assets/RAW_NIKON_D3X.NEF"
+    ))
+    .expect("Failed to open file");
+    assert!(p.raw_image().is_err());
+    p.unpack().expect("Failed to unpack");
+    let raw = p.raw_image().expect("Missing raw image");
+    assert_eq!(raw.width(), p.sizes().width as usize);
+    assert_eq!(raw.height(), p.sizes().height as usize);
+
+    let pattern = raw.cfa_pattern().expect("Missing cfa pattern");
+    let colors = pattern.colors();
+    assert_eq!(CfaPattern::from_colors(colors), Some(pattern));
+    for (row, col) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+        assert_eq!(raw.color_char(row, col), Some(colors[row][col]));
+        assert_eq!(raw.color_char(row + 2, col + 2), Some(colors[row][col]));
+    }
+
+    let (top, left) = (raw.top_margin(), raw.left_margin());
+    assert_eq!(raw.visible(10, 20), raw.get(top + 10, left + 20));
+    assert!(raw.visible(10, 20).is_some());
+    assert_eq!(raw.get(raw.raw_height(), 0), None);
+    assert_eq!(raw.visible(0, raw.width()), None);
+    assert_eq!(raw.rows().count(), raw.raw_height());
+}
+
+#[test]
+fn raw_data_layout() {
+    use libraw_r::raw::RawData;
+    use libraw_r::*;
+    let mut p = Processor::default();
+    p.open(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/assets/RAW_NIKON_D3X.NEF"
+    ))
+    .expect("Failed to open file");
+    assert!(matches!(p.raw_data(), Err(LibrawError::RawDataMissing)));
+    p.unpack().expect("Failed to unpack");
+    let data = p.raw_data().expect("Missing raw data");
+    assert_eq!(data.channels(), 1);
+    assert!(!data.is_float());
+    let RawData::Bayer(raw) = data else {
+        panic!("Expected bayer data");
+    };
+    let sizes = p.sizes();
+    assert_eq!(raw.raw_width(), sizes.raw_width as usize);
+    assert_eq!(raw.raw_height(), sizes.raw_height as usize);
+    assert!(raw.as_slice().len() >= raw.raw_width() * raw.raw_height());
+    let last = (raw.raw_height() - 1, raw.raw_width() - 1);
+    assert!(raw.get(last.0, last.1).is_some());
+    assert_eq!(raw.get(last.0, last.1 + 1), None);
+    assert_eq!(
+        raw.get(5, 7),
+        p.raw_image().expect("Missing raw image").get(5, 7)
+    );
+}
+
+#[test]
+fn image4_buffer() {
+    use libraw_r::*;
+    let mut p = Processor::default();
+    p.open(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/assets/RAW_NIKON_D3X.NEF"
+    ))
+    .expect("Failed to open file");
+    p.unpack().expect("Failed to unpack");
+    assert!(matches!(p.image4(), Err(LibrawError::RawDataMissing)));
+    assert!(p.subtract_black().is_err());
+
+    p.raw2image().expect("Failed to raw2image");
+    let (row, col) = (10, 20);
+    let value = *p.raw_image().unwrap().visible(row, col).unwrap();
+    let image = p.image4().expect("Missing image");
+    assert!(!image.is_half_size());
+    assert_eq!(image.width(), p.sizes().iwidth as usize);
+    assert_eq!(image.height(), p.sizes().iheight as usize);
+    assert_eq!(image.rows().count(), image.height());
+    let pixel = *image.get(row, col).unwrap();
+    // Only the slot of the color of the pixel is filled
+    assert!(pixel.iter().filter(|v| **v != 0).count() <= 1);
+    assert_eq!(pixel.iter().max(), Some(&value));
+
+    p.subtract_black().expect("Failed to subtract black");
+    let subtracted = *p.image4().unwrap().get(row, col).unwrap();
+    assert!(subtracted.iter().max() <= pixel.iter().max());
+
+    let width = p.sizes().iwidth as usize;
+    p.image4_mut().unwrap()[row * width + col] = [1, 2, 3, 4];
+    assert_eq!(p.image4().unwrap().get(row, col), Some(&[1, 2, 3, 4]));
+
+    p.free_image();
+    assert!(matches!(p.image4(), Err(LibrawError::RawDataMissing)));
+
+    p.params().half_size = 1;
+    p.raw2image().expect("Failed to raw2image");
+    let image = p.image4().expect("Missing image");
+    assert!(image.is_half_size());
+    assert_eq!(image.width(), (p.sizes().width as usize).div_ceil(2));
+}
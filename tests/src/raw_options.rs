@@ -0,0 +1,69 @@
+#[test]
+fn raw_options_survive_open() {
+    use libraw_r::raw_options::*;
+    use libraw_r::*;
+    let options = RawOptions::new()
+        .with_max_raw_memory_mb(512)
+        .with_dng_stages(true, true, true)
+        .with_specials(RawSpecials::SONY_ARW2_BASE_ONLY)
+        .with_custom_camera("1234,100,100,0,0,0,0,0,0x94,0,0,0,Make,Model,0");
+    let mut p = Processor::builder()
+        .with_raw_options(options.clone())
+        .expect("Failed to set raw options")
+        .build();
+    assert_eq!(p.raw_options(), options);
+    assert!(p
+        .raw_options()
+        .flags()
+        .contains(RawFlags::DNG_STAGE3_IF_PRESENT));
+
+    p.open(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/assets/RAW_NIKON_D3X.NEF"
+    ))
+    .expect("Failed to open file");
+    p.recycle().expect("Failed to recycle");
+    assert_eq!(p.raw_options(), options);
+}
+
+#[test]
+fn raw_options_validation() {
+    use libraw_r::*;
+    let mut p = Processor::default();
+    assert!(matches!(
+        p.set_raw_options(RawOptions::new().with_max_raw_memory_mb(0)),
+        Err(LibrawError::InvalidOption(_))
+    ));
+    assert!(p
+        .set_raw_options(RawOptions::new().with_custom_camera("nul\0"))
+        .is_err());
+}
+
+#[test]
+fn raw_options_keep_selected_frame() {
+    use libraw_r::raw_options::*;
+    use libraw_r::*;
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/RAW_NIKON_D3X.NEF");
+    let options = RawOptions::new().with_specials(RawSpecials::SRAW_NO_RGB);
+    let mut p = Processor::builder()
+        .with_raw_options(options.clone())
+        .expect("Failed to set raw options")
+        .build();
+
+    p.select_frame(1);
+    p.recycle().expect("Failed to recycle");
+    assert_eq!(p.selected_frame(), 1);
+    assert_eq!(p.raw_options(), options.clone().with_shot_select(1));
+
+    // Iterating the frames reopens the file, which must not go back to the options' frame
+    p.select_frame(0);
+    p.open(path).expect("Failed to open file");
+    let frames = p
+        .frames()
+        .collect::<Result<Vec<_>, _>>()
+        .expect("Failed to process frames");
+    assert_eq!(frames.len(), 1);
+    assert!(p.unpack_frame(1).is_err());
+    assert_eq!(p.selected_frame(), 0);
+    assert_eq!(p.raw_options(), options);
+}
@@ -0,0 +1,157 @@
+#[test]
+fn render_variants() {
+    use libraw_r::options::*;
+    use libraw_r::*;
+    let mut p = Processor::default();
+    p.open(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/assets/RAW_NIKON_D3X.NEF"
+    ))
+    .expect("Failed to open file");
+    let half = ProcessingOptions {
+        half_size: true,
+        ..Default::default()
+    };
+    let preview = p.render(&half).expect("Failed to render preview");
+    let full = p
+        .render(&ProcessingOptions {
+            output_bps: BitDepth::Sixteen,
+            gamma: Gamma::Linear,
+            ..Default::default()
+        })
+        .expect("Failed to render full size");
+    assert_eq!(full.bits(), 16);
+    assert!(full.width() >= preview.width() * 2 - 1);
+    assert!(full.height() >= preview.height() * 2 - 1);
+
+    // Rendering again from the same unpacked raw gives the same result
+    let again = p.render(&half).expect("Failed to render preview again");
+    assert_eq!(again.as_slice_u8(), preview.as_slice_u8());
+}
+
+#[test]
+fn copy_into_buffer() {
+    use libraw_r::*;
+    let mut p = Processor::default();
+    p.open(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/assets/RAW_NIKON_D3X.NEF"
+    ))
+    .expect("Failed to open file");
+    p.set_processing_options(&ProcessingOptions {
+        half_size: true,
+        ..Default::default()
+    })
+    .expect("Failed to set options");
+    p.unpack().expect("Failed to unpack");
+    let mut buffer = vec![0u8; 64];
+    assert!(matches!(
+        p.copy_into(&mut buffer, 64, PixelLayout::Bgra),
+        Err(LibrawError::InternalError(
+            error::InternalLibrawError::OutOfOrderCall
+        ))
+    ));
+    p.dcraw_process().expect("Failed to process");
+
+    let format = p.output_format();
+    assert_eq!((format.colors, format.bits), (3, 8));
+    let stride = format.row_len(PixelLayout::Bgra) + 64;
+    let mut buffer = vec![0u8; format.buffer_len(PixelLayout::Bgra, stride)];
+    assert_eq!(
+        p.copy_into(&mut buffer, stride, PixelLayout::Bgra)
+            .expect("Failed to render"),
+        format
+    );
+    assert!(matches!(
+        p.copy_into(&mut buffer[1..], stride, PixelLayout::Bgra),
+        Err(LibrawError::InvalidOutputBuffer(_))
+    ));
+
+    let image = p
+        .dcraw_process_make_mem_image()
+        .expect("Failed to make image");
+    assert_eq!(
+        (image.width(), image.height()),
+        (format.width, format.height)
+    );
+    let rgb = image.as_slice_u8();
+    let width = format.width as usize;
+    for (row, line) in buffer.chunks(stride).enumerate() {
+        for col in 0..width {
+            let [r, g, b] = [0, 1, 2].map(|c| rgb[(row * width + col) * 3 + c]);
+            assert_eq!(line[col * 4..col * 4 + 4], [b, g, r, 0xff]);
+        }
+    }
+}
+
+#[test]
+fn processed_image_conversions() {
+    use libraw_r::options::*;
+    use libraw_r::*;
+    let mut p = Processor::default();
+    p.open(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/assets/RAW_NIKON_D3X.NEF"
+    ))
+    .expect("Failed to open file");
+    let image = p
+        .render(&ProcessingOptions {
+            half_size: true,
+            output_bps: BitDepth::Sixteen,
+            ..Default::default()
+        })
+        .expect("Failed to render");
+    let pixels = image.width() as usize * image.height() as usize;
+    assert_eq!(image.rgb16().expect("Not 16 bit rgb").len(), pixels);
+    assert!(matches!(image.rgb8(), Err(LibrawError::InvalidColor(16))));
+    assert!(matches!(
+        image.gray16(),
+        Err(LibrawError::InvalidImageLayout(_))
+    ));
+
+    // Can be moved to another thread
+    let samples = std::thread::spawn(move || image.into_vec_u16())
+        .join()
+        .unwrap()
+        .expect("Failed to convert");
+    assert_eq!(samples.len(), pixels * 3);
+
+    let image = p
+        .render(&ProcessingOptions {
+            half_size: true,
+            ..Default::default()
+        })
+        .expect("Failed to render");
+    let rgb = image.rgb8().expect("Not 8 bit rgb");
+    assert_eq!(rgb.len(), pixels);
+    assert_eq!(rgb.concat(), image.as_slice_u8()[..pixels * 3]);
+    assert!(matches!(image.rgb16(), Err(LibrawError::InvalidColor(8))));
+    #[allow(deprecated)]
+    {
+        assert_eq!(image.as_slice::<u8>(), image.as_slice_u8());
+        assert!(std::panic::catch_unwind(|| image.as_slice::<u16>().len()).is_err());
+    }
+
+    #[cfg(feature = "jpeg")]
+    {
+        let image = p
+            .render(&ProcessingOptions {
+                half_size: true,
+                ..Default::default()
+            })
+            .expect("Failed to render");
+        let (width, height) = (image.width(), image.height());
+        let rgb = image::RgbImage::try_from(image).expect("Failed to convert");
+        assert_eq!(rgb.dimensions(), (width, height));
+        let image = p
+            .render(&ProcessingOptions {
+                half_size: true,
+                ..Default::default()
+            })
+            .expect("Failed to render");
+        assert!(matches!(
+            image::DynamicImage::try_from(image),
+            Ok(image::DynamicImage::ImageRgb8(_))
+        ));
+    }
+}
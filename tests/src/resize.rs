@@ -0,0 +1,119 @@
+#[cfg(feature = "jpeg")]
+#[test]
+fn resize_sizes() {
+    use libraw_r::resize::*;
+    let fit = Resize::new(800, 800);
+    assert_eq!(fit.output_size(6048, 4032), (800, 533));
+    assert!(fit.allows_half_size(6048, 4032));
+    assert!(!fit.allows_half_size(1200, 800));
+
+    let fill = Resize {
+        mode: ResizeMode::Fill,
+        ..Resize::new(800, 800)
+    };
+    assert_eq!(fill.output_size(6048, 4032), (800, 800));
+    // Has to cover the height, 800 / 4032 is less than half
+    assert!(fill.allows_half_size(6048, 4032));
+    assert!(!fill.allows_half_size(6048, 1200));
+
+    let exact = Resize {
+        mode: ResizeMode::Exact,
+        ..Resize::new(4000, 100)
+    };
+    assert!(!exact.allows_half_size(6048, 4032));
+    assert!(Resize::new(0, 10).validate().is_err());
+}
+
+#[cfg(feature = "jpeg")]
+#[test]
+fn to_jpeg_with_resolution_resizes() {
+    use libraw_r::*;
+    let mut p = Processor::default();
+    p.open(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/assets/RAW_NIKON_D3X.NEF"
+    ))
+    .expect("Failed to open file");
+    p.set_embed_exif(false);
+    let jpeg = p
+        .to_jpeg_with_resolution((640u32, 640u32), false, 90)
+        .expect("Failed to encode");
+    let image = image::load_from_memory_with_format(&jpeg, image::ImageFormat::Jpeg)
+        .expect("Failed to decode");
+    assert_eq!(image.width().max(image.height()), 640);
+    // half_size is only used for this run
+    assert_eq!(p.params().half_size, 0);
+}
+
+#[cfg(feature = "png")]
+#[test]
+fn encode_resized() {
+    use libraw_r::options::*;
+    use libraw_r::resize::*;
+    use libraw_r::*;
+    let mut p = Processor::default();
+    p.open(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/assets/RAW_NIKON_D3X.NEF"
+    ))
+    .expect("Failed to open file");
+    let options = ProcessingOptions {
+        output_color: OutputColorSpace::AdobeRgb,
+        user_saturation: Some(12000),
+        ..Default::default()
+    };
+    p.set_processing_options(&options)
+        .expect("Failed to set options");
+    let png = p
+        .encode(
+            OutputFormat::Png,
+            &EncodeOptions {
+                resize: Some(Resize {
+                    mode: ResizeMode::Fill,
+                    filter: ResizeFilter::CatmullRom,
+                    sharpen: Some(Sharpen::default()),
+                    ..Resize::new(300, 200)
+                }),
+                ..Default::default()
+            },
+        )
+        .expect("Failed to encode");
+    let image = image::load_from_memory_with_format(&png, image::ImageFormat::Png)
+        .expect("Failed to decode");
+    assert_eq!((image.width(), image.height()), (300, 200));
+    // Processed with half_size, the options are restored afterwards
+    assert_eq!(p.processing_options(), options);
+}
+
+#[cfg(feature = "png")]
+#[test]
+fn encode_resized_keeps_raw_params() {
+    use libraw_r::resize::*;
+    use libraw_r::*;
+    let mut p = Processor::default();
+    p.open(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/assets/RAW_NIKON_D3X.NEF"
+    ))
+    .expect("Failed to open file");
+    // Set directly, not through ProcessingOptions
+    p.params().user_qual = 0;
+    p.params().highlight = 2;
+    p.params().use_camera_wb = 1;
+    let png = p
+        .encode(
+            OutputFormat::Png,
+            &EncodeOptions {
+                resize: Some(Resize::new(300, 300)),
+                ..Default::default()
+            },
+        )
+        .expect("Failed to encode");
+    let image = image::load_from_memory_with_format(&png, image::ImageFormat::Png)
+        .expect("Failed to decode");
+    assert_eq!(image.width().max(image.height()), 300);
+    assert_eq!(p.params().user_qual, 0);
+    assert_eq!(p.params().highlight, 2);
+    assert_eq!(p.params().use_camera_wb, 1);
+    assert_eq!(p.params().half_size, 0);
+}
@@ -0,0 +1,64 @@
+#[test]
+fn best_thumbnail() {
+    use libraw_r::*;
+    let mut p = Processor::default();
+    p.open(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/assets/RAW_NIKON_D3X.NEF"
+    ))
+    .expect("Failed to open file");
+    let thumbnails = p.thumbnails().collect::<Vec<_>>();
+    assert!(!thumbnails.is_empty());
+
+    // Anything larger than every preview falls back to the largest one
+    let largest = p
+        .best_thumbnail((u32::MAX, u32::MAX))
+        .expect("No thumbnail");
+    assert!(thumbnails
+        .iter()
+        .all(|t| t.width as u32 * t.height as u32 <= largest.width as u32 * largest.height as u32));
+
+    let smallest = p.best_thumbnail((1u32, 1u32)).expect("No thumbnail");
+    assert!(thumbnails.iter().all(
+        |t| t.width as u32 * t.height as u32 >= smallest.width as u32 * smallest.height as u32
+    ));
+    assert_eq!(p.thumbnail().twidth, smallest.width);
+}
+
+#[test]
+fn decode_thumbnail() {
+    use libraw_r::thumbnail::ThumbnailData;
+    use libraw_r::*;
+    let mut p = Processor::default();
+    p.open(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/assets/RAW_NIKON_D3X.NEF"
+    ))
+    .expect("Failed to open file");
+    let thumbnail = p.decode_thumbnail().expect("Failed to decode thumbnail");
+    assert!(thumbnail.width > 0 && thumbnail.height > 0);
+    match thumbnail.data {
+        ThumbnailData::Jpeg(jpeg) => assert!(jpeg.starts_with(&[0xff, 0xd8])),
+        ThumbnailData::Rgb8(rgb) => {
+            assert_eq!(rgb.len(), (thumbnail.width * thumbnail.height * 3) as usize)
+        }
+        other => panic!("Unexpected thumbnail {:?}", other),
+    }
+}
+
+#[cfg(feature = "jpeg")]
+#[test]
+fn best_jpeg_keeps_default_thumbnail() {
+    use libraw_r::*;
+    let mut p = Processor::default();
+    p.open(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/assets/RAW_NIKON_D3X.NEF"
+    ))
+    .expect("Failed to open file");
+    let default = p.current_thumbnail().expect("No default thumbnail");
+    let jpeg = p.best_jpeg((1u32, 1u32)).expect("Failed to get jpeg");
+    assert!(jpeg.starts_with(&[0xff, 0xd8]));
+    assert_eq!(p.current_thumbnail(), Some(default));
+    assert_eq!(p.thumbnail().twidth, default.width);
+}
@@ -0,0 +1,38 @@
+#[test]
+fn typestate_pipeline() {
+    use libraw_r::typestate::TypedProcessor;
+    let processed = TypedProcessor::new()
+        .open(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/assets/RAW_NIKON_D3X.NEF"
+        ))
+        .expect("Failed to open file")
+        .unpack()
+        .expect("Failed to unpack")
+        .process()
+        .expect("Failed to process");
+    let image = processed
+        .make_mem_image()
+        .expect("Failed to make mem image");
+    assert!(image.width() > 0 && image.height() > 0);
+    let _empty = processed.recycle();
+}
+
+#[test]
+fn typestate_failed_transition() {
+    use libraw_r::typestate::TypedProcessor;
+    let Err(error) = TypedProcessor::new().open("/nonexistent/image.nef") else {
+        panic!("Opened a missing file");
+    };
+    assert!(matches!(error.error(), libraw_r::LibrawError::IoError(_)));
+    // The processor can be reused after the error
+    let opened = error
+        .into_processor()
+        .recycle()
+        .open(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/assets/RAW_NIKON_D3X.NEF"
+        ))
+        .expect("Failed to open file");
+    assert!(opened.sizes().width > 0);
+}